@@ -1,15 +1,14 @@
 use crate::error::Error;
 use std::io::Read;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize)]
 pub enum Data {
     Base64(String),
     ZoneIdentifier(String),
 }
 
 impl Data {
-    #[allow(dead_code)] // Used in tests
-    pub fn from_buffer(buffer: &Vec<u8>, is_zone_identifier: bool) -> crate::Result<Self> {
+    pub fn from_buffer(buffer: &[u8], is_zone_identifier: bool) -> crate::Result<Self> {
         if is_zone_identifier {
             let mut reader = std::io::Cursor::new(buffer);
             let mut data = String::new();
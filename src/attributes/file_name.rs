@@ -1,17 +1,21 @@
-use crate::error::Error;
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::ReadBytesExt;
 use chrono::{DateTime, Utc};
 use std::io::{Read, Seek};
 //
 use crate::raw::FileReference;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FileName {
     pub parent_file_reference: FileReference,
     pub creation_time: DateTime<Utc>,
     pub modification_time: DateTime<Utc>,
     pub mft_modification_time: DateTime<Utc>,
     pub access_time: DateTime<Utc>,
+    // Raw FILETIME values of the times above
+    pub creation_time_raw: u64,
+    pub modification_time_raw: u64,
+    pub mft_modification_time_raw: u64,
+    pub access_time_raw: u64,
     pub allocated_size: u64,
     pub real_size: u64,
     pub flags: u32,
@@ -38,19 +42,13 @@ impl FileName {
         read_value!(reader, real_size, read_u64);
         read_value!(reader, flags, read_u32);
         read_value!(reader, reparse_value, read_u32);
-        let name_length = reader
-            .read_u8()
-            .map_err(|e| Error::into_value_read_error(e.into(), "name_length", "read_u8"))?;
-        let name_space = reader
-            .read_u8()
-            .map_err(|e| Error::into_value_read_error(e.into(), "name_space", "read_u8"))?;
+        read_value!(reader, name_length, read_u8);
+        read_value!(reader, name_space, read_u8);
 
         let mut name = String::new();
         for _ in 0..name_length {
-            let c = reader
-                .read_u16::<LittleEndian>()
-                .map_err(|e| Error::into_value_read_error(e.into(), "name_char", "read_u16"))?;
-            name.push(c as u8 as char);
+            read_value!(reader, name_char, read_u16);
+            name.push(name_char as u8 as char);
         }
 
         Ok(Self {
@@ -59,6 +57,10 @@ impl FileName {
             modification_time: super::convert_u64_to_datetime(modification_time),
             mft_modification_time: super::convert_u64_to_datetime(mft_modification_time),
             access_time: super::convert_u64_to_datetime(access_time),
+            creation_time_raw: creation_time,
+            modification_time_raw: modification_time,
+            mft_modification_time_raw: mft_modification_time,
+            access_time_raw: access_time,
             allocated_size,
             real_size,
             flags,
@@ -3,28 +3,42 @@ mod data;
 mod file_name;
 mod standard_info;
 
-use chrono::{DateTime, Duration, NaiveDate, Utc};
+use chrono::{DateTime, Utc};
 
 pub use attributes_list::{AttributeList, AttributeListItem};
 pub use data::Data;
 pub use file_name::FileName;
 pub use standard_info::StandardInformation;
 
+// Seconds between 1601-01-01 and the unix epoch
+const FILETIME_UNIX_EPOCH_SECONDS: i64 = 11_644_473_600;
+const FILETIME_TICKS_PER_SECOND: u64 = 10_000_000;
+
 // https://learn.microsoft.com/en-us/windows/win32/api/minwinbase/ns-minwinbase-filetime
 // Contains a 64-bit value representing the number of 100-nanosecond intervals since January 1, 1601 (UTC).
+// Returns None for zero (unset) values and anything chrono can't represent
+pub fn filetime_to_datetime(timestamp: u64) -> Option<DateTime<Utc>> {
+    if timestamp == 0 {
+        return None;
+    }
+    let seconds = (timestamp / FILETIME_TICKS_PER_SECOND) as i64 - FILETIME_UNIX_EPOCH_SECONDS;
+    let nanoseconds = (timestamp % FILETIME_TICKS_PER_SECOND) as u32 * 100;
+    DateTime::from_timestamp(seconds, nanoseconds)
+}
+
+// Full 100ns precision, zero and unrepresentable values are returned as 1601-01-01 00:00:00
 pub fn convert_u64_to_datetime(timestamp: u64) -> DateTime<Utc> {
-    // From 1/1/1601 00:00:00.0 add timestamp as microseconds
-    DateTime::from_utc(
-        NaiveDate::from_ymd(1601, 1, 1).and_hms_nano(0, 0, 0, 0)
-            + Duration::microseconds((timestamp / 10) as i64),
-        Utc,
-    )
+    filetime_to_datetime(timestamp).unwrap_or_else(|| {
+        DateTime::from_timestamp(-FILETIME_UNIX_EPOCH_SECONDS, 0).unwrap_or_default()
+    })
 }
 
 #[cfg(test)]
 mod iterator_tests {
     use super::*;
+    use chrono::{NaiveDate, TimeZone};
     #[test]
+    #[allow(deprecated)]
     fn timestamp_test() {
         let data: u64 = 0x989680;
         let date = convert_u64_to_datetime(data);
@@ -33,4 +47,26 @@ mod iterator_tests {
             DateTime::<Utc>::from_utc(NaiveDate::from_ymd(1601, 1, 1).and_hms(0, 0, 1), Utc)
         );
     }
+
+    #[test]
+    fn timestamp_precision_test() {
+        // 2023-01-01T00:00:00.1234567Z
+        let date = convert_u64_to_datetime(133_170_048_001_234_567);
+        assert_eq!(date.timestamp(), 1_672_531_200);
+        assert_eq!(date.timestamp_subsec_nanos(), 123_456_700);
+    }
+
+    #[test]
+    fn timestamp_edge_test() {
+        assert_eq!(filetime_to_datetime(0), None);
+        assert_eq!(
+            convert_u64_to_datetime(0),
+            Utc.with_ymd_and_hms(1601, 1, 1, 0, 0, 0).unwrap()
+        );
+        // Hostile values must not panic
+        for timestamp in [1, i64::MAX as u64, i64::MAX as u64 + 1, u64::MAX] {
+            let _ = convert_u64_to_datetime(timestamp);
+        }
+        assert!(filetime_to_datetime(u64::MAX).is_some());
+    }
 }
@@ -8,6 +8,11 @@ pub struct StandardInformation {
     pub modification_time: DateTime<Utc>,
     pub mft_modification_time: DateTime<Utc>,
     pub access_time: DateTime<Utc>,
+    // Raw FILETIME values of the times above
+    pub creation_time_raw: u64,
+    pub modification_time_raw: u64,
+    pub mft_modification_time_raw: u64,
+    pub access_time_raw: u64,
     pub file_attributes: u32,
     pub max_versions: u32,
     pub version_number: u32,
@@ -37,6 +42,10 @@ impl StandardInformation {
             modification_time: super::convert_u64_to_datetime(modification_time),
             mft_modification_time: super::convert_u64_to_datetime(mft_modification_time),
             access_time: super::convert_u64_to_datetime(access_time),
+            creation_time_raw: creation_time,
+            modification_time_raw: modification_time,
+            mft_modification_time_raw: mft_modification_time,
+            access_time_raw: access_time,
             file_attributes,
             max_versions,
             version_number,
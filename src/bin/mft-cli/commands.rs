@@ -0,0 +1,506 @@
+use crate::{fatal, open, parse_drive, Failure};
+use mft::diff::Diff;
+use mft::raw::{Attribute, AttributeData, RecordStatus};
+use mft::stream::{self, Stream, Volume};
+use mft::tree::{Child, DirectoryTree, ROOT_ENTRY};
+use mft::usn::{UsnJournal, UsnRecord};
+use mft::{Parser, ParserSettings, Record};
+use regex::{Regex, RegexBuilder};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+#[derive(Debug, clap::Subcommand)]
+pub enum Command {
+    /// Print the header and every attribute of one entry
+    Stat {
+        #[command(flatten)]
+        source: Source,
+
+        /// Entry number or path
+        target: String,
+    },
+    /// List the entries whose parent is a directory
+    Ls {
+        #[command(flatten)]
+        source: Source,
+
+        /// Entry number or path of the directory
+        directory: String,
+    },
+    /// Print an indented directory tree
+    Tree {
+        #[command(flatten)]
+        source: Source,
+
+        /// Entry number or path of the directory to start at, the root by default
+        directory: Option<String>,
+
+        /// Levels of directories to print below the start
+        #[arg(long, value_name = "N")]
+        depth: Option<usize>,
+    },
+    /// Print the paths of entries matching every search given
+    #[command(group(clap::ArgGroup::new("search").required(true).multiple(true)))]
+    Find {
+        #[command(flatten)]
+        source: Source,
+
+        /// Filename, ignoring case
+        #[arg(long, group = "search")]
+        name: Option<String>,
+
+        /// Filename glob with * and ?, ignoring case
+        #[arg(long, group = "search")]
+        glob: Option<String>,
+
+        /// Regex matched against the full path
+        #[arg(long, group = "search", value_name = "REGEX")]
+        regex: Option<String>,
+    },
+    /// Write the contents of a $DATA stream to stdout
+    Cat {
+        #[command(flatten)]
+        source: Source,
+
+        /// Entry number or path, followed by :name for a named stream
+        target: String,
+
+        /// Volume image the $MFT came from, needed for non-resident streams
+        #[arg(long, value_name = "FILE")]
+        image: Option<PathBuf>,
+    },
+    /// List files created, deleted, renamed, moved and modified between two snapshots of a volume
+    Diff {
+        /// Earlier $MFT
+        old: PathBuf,
+
+        /// Later $MFT of the same volume
+        new: PathBuf,
+
+        /// Drive letter paths start with, paths start with {Root} without one
+        #[arg(short, long, value_name = "LETTER", value_parser = parse_drive)]
+        drive: Option<char>,
+
+        /// Write one JSON object per change instead of CSV
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the records of a $UsnJrnl:$J change journal with their paths from the $MFT
+    Usn {
+        #[command(flatten)]
+        source: Source,
+
+        /// $UsnJrnl:$J stream extracted from the same volume
+        journal: PathBuf,
+
+        /// Write one JSON object per record instead of CSV
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+// The $MFT a subcommand reads
+#[derive(Debug, clap::Args)]
+pub struct Source {
+    /// $MFT file to read, - reads it from stdin
+    input: PathBuf,
+
+    /// Drive letter paths start with, paths start with {Root} without one
+    #[arg(short, long, value_name = "LETTER", value_parser = parse_drive)]
+    drive: Option<char>,
+
+    /// Memory map the input instead of reading it through a buffer
+    #[arg(long)]
+    mmap: bool,
+}
+
+impl Source {
+    fn open(&self) -> Result<Parser, Failure> {
+        let mut settings = ParserSettings::new().mmap(self.mmap);
+        if let Some(drive) = self.drive {
+            settings = settings.drive_char(drive);
+        }
+        open(&self.input, settings)
+    }
+}
+
+impl Command {
+    pub fn run(&self) -> Result<(), Failure> {
+        match self {
+            Command::Stat { source, target } => {
+                let mut parser = source.open()?;
+                let entry_id = resolve(&mut parser, target)?;
+                write_lines(&stat(&mut parser, entry_id)?)
+            }
+            Command::Ls { source, directory } => {
+                let mut parser = source.open()?;
+                let entry_id = resolve_directory(&mut parser, directory)?;
+                let tree = parser.directory_tree();
+                let lines: Vec<String> = tree
+                    .children(entry_id)
+                    .iter()
+                    .map(|child| listing(tree, child))
+                    .collect();
+                write_lines(&lines)
+            }
+            Command::Tree {
+                source,
+                directory,
+                depth,
+            } => {
+                let mut parser = source.open()?;
+                let entry_id = match directory {
+                    Some(directory) => resolve_directory(&mut parser, directory)?,
+                    None => ROOT_ENTRY,
+                };
+                let path = parser
+                    .get_file_path(entry_id)
+                    .map_err(fatal(&format!("Entry {}", entry_id)))?;
+                let mut lines = vec![path.display().to_string()];
+                let tree = parser.directory_tree();
+                let mut walk = tree.walk(entry_id);
+                if let Some(depth) = depth {
+                    walk = walk.max_depth(*depth);
+                }
+                for entry in walk {
+                    let is_directory = tree.node(entry.entry_id).is_some_and(|n| n.is_directory);
+                    let suffix = if is_directory { "/" } else { "" };
+                    lines.push(format!(
+                        "{}{}{}",
+                        "  ".repeat(entry.depth),
+                        entry.name,
+                        suffix
+                    ));
+                }
+                write_lines(&lines)
+            }
+            Command::Find {
+                source,
+                name,
+                glob,
+                regex,
+            } => {
+                let glob = glob.as_deref().map(glob_regex).transpose()?;
+                let regex = regex
+                    .as_deref()
+                    .map(|r| Regex::new(r).map_err(|e| Failure::Usage(e.to_string())))
+                    .transpose()?;
+                let name = name.as_deref().map(str::to_lowercase);
+                let mut parser = source.open()?;
+                let mut lines = Vec::new();
+                for_each_record(&mut parser, |record| {
+                    let path = record.path.display().to_string();
+                    let filename = record.filename.as_deref().unwrap_or_default();
+                    if name.as_ref().is_none_or(|n| filename.to_lowercase() == *n)
+                        && glob.as_ref().is_none_or(|g| g.is_match(filename))
+                        && regex.as_ref().is_none_or(|r| r.is_match(&path))
+                    {
+                        lines.push(path);
+                    }
+                });
+                write_lines(&lines)
+            }
+            Command::Cat {
+                source,
+                target,
+                image,
+            } => {
+                let (target, stream_name) = split_stream(target);
+                let mut parser = source.open()?;
+                let entry_id = resolve(&mut parser, target)?;
+                let stream = stream::locate(&mut parser, entry_id, stream_name)
+                    .map_err(fatal(&format!("Entry {}", entry_id)))?;
+                let mut writer = BufWriter::new(std::io::stdout().lock());
+                match stream {
+                    Stream::Resident(data) => writer.write_all(&data).map_err(fatal("stdout"))?,
+                    Stream::NonResident { runs, size } => {
+                        let image = image.as_ref().ok_or_else(|| {
+                            Failure::Usage(format!(
+                                "The stream of entry {} is not resident, --image is needed to read it",
+                                entry_id
+                            ))
+                        })?;
+                        let file =
+                            File::open(image).map_err(fatal(&image.display().to_string()))?;
+                        Volume::new(file)
+                            .and_then(|mut volume| volume.copy_to(&runs, size, &mut writer))
+                            .map_err(fatal(&image.display().to_string()))?;
+                    }
+                }
+                writer.flush().map_err(fatal("stdout"))
+            }
+            Command::Diff {
+                old,
+                new,
+                drive,
+                json,
+            } => {
+                let mut settings = ParserSettings::new();
+                if let Some(drive) = drive {
+                    settings = settings.drive_char(*drive);
+                }
+                let mut old = open(old, settings.clone())?;
+                let mut new = open(new, settings)?;
+                let diff = Diff::new(&mut old, &mut new);
+                if diff.skipped > 0 {
+                    log::warn!(
+                        "{} entries could not be read and were left out",
+                        diff.skipped
+                    );
+                }
+                let writer = BufWriter::new(std::io::stdout().lock());
+                match json {
+                    true => diff.write_json(writer),
+                    false => diff.write_csv(writer),
+                }
+                .map_err(fatal("stdout"))
+            }
+            Command::Usn {
+                source,
+                journal,
+                json,
+            } => {
+                let mut parser = source.open()?;
+                let name = journal.display().to_string();
+                let file = File::open(journal).map_err(fatal(&name))?;
+                let mut records = UsnJournal::new(file);
+                let mut writer = BufWriter::new(std::io::stdout().lock());
+                if !json {
+                    writeln!(writer, "{}", UsnRecord::CSV_HEADER).map_err(fatal("stdout"))?;
+                }
+                for record in records.by_ref() {
+                    let mut record = record.map_err(fatal(&name))?;
+                    record.correlate(&mut parser);
+                    match json {
+                        true => serde_json::to_writer(&mut writer, &record)
+                            .map_err(fatal("stdout"))
+                            .and_then(|_| writeln!(writer).map_err(fatal("stdout")))?,
+                        false => {
+                            writeln!(writer, "{}", record.to_csv()).map_err(fatal("stdout"))?
+                        }
+                    }
+                }
+                if records.skipped > 0 {
+                    log::warn!("{} bytes of {} were not USN records", records.skipped, name);
+                }
+                writer.flush().map_err(fatal("stdout"))
+            }
+        }
+    }
+}
+
+fn write_lines(lines: &[String]) -> Result<(), Failure> {
+    let mut writer = BufWriter::new(std::io::stdout().lock());
+    for line in lines {
+        writeln!(writer, "{}", line).map_err(fatal("stdout"))?;
+    }
+    writer.flush().map_err(fatal("stdout"))
+}
+
+// Entry number, or the entry with a path. An entry in use is picked over deleted ones with the
+// same path
+fn resolve(parser: &mut Parser, target: &str) -> Result<u64, Failure> {
+    if let Ok(entry_id) = target.parse::<u64>() {
+        return match entry_id < parser.block_count() {
+            true => Ok(entry_id),
+            false => Err(Failure::Fatal(format!("No entry {}", entry_id))),
+        };
+    }
+    let found = parser.lookup_path(target);
+    let tree = parser.directory_tree();
+    found
+        .iter()
+        .find(|id| tree.node(**id).is_some_and(|n| !n.is_deleted))
+        .or(found.first())
+        .copied()
+        .ok_or_else(|| Failure::Fatal(format!("No entry with the path {}", target)))
+}
+
+fn resolve_directory(parser: &mut Parser, target: &str) -> Result<u64, Failure> {
+    let entry_id = resolve(parser, target)?;
+    let entry = parser
+        .entry(entry_id)
+        .map_err(fatal(&format!("Entry {}", entry_id)))?;
+    match entry.header.flags & 0x02 != 0 {
+        true => Ok(entry_id),
+        false => Err(Failure::Fatal(format!("{} is not a directory", target))),
+    }
+}
+
+// Calls f with every entry that parses into a record, one at a time, the rest are skipped
+fn for_each_record<F: FnMut(Record)>(parser: &mut Parser, mut f: F) {
+    for entry_id in 0..parser.block_count() {
+        let block = match parser.block_at(entry_id) {
+            Ok(block) if block.status == RecordStatus::Valid => block,
+            _ => continue,
+        };
+        if let Ok(record) = Record::from(parser, &block) {
+            f(record);
+        }
+    }
+}
+
+// Type, deleted marker and entry before the name, like fls
+fn listing(tree: &DirectoryTree, child: &Child) -> String {
+    let node = tree.node(child.entry_id);
+    format!(
+        "{} {}{}:\t{}",
+        if node.is_some_and(|n| n.is_directory) {
+            "d"
+        } else {
+            "f"
+        },
+        if node.is_some_and(|n| n.is_deleted) {
+            "* "
+        } else {
+            ""
+        },
+        child.entry_id,
+        child.name
+    )
+}
+
+fn glob_regex(glob: &str) -> Result<Regex, Failure> {
+    let mut regex = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    RegexBuilder::new(&regex)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| Failure::Usage(e.to_string()))
+}
+
+// Splits a stream name off at a : in the last part of the path, so drive letters are kept
+fn split_stream(target: &str) -> (&str, Option<&str>) {
+    let last = target.rfind(['/', '\\']).map_or(0, |i| i + 1);
+    match target[last..].find(':') {
+        Some(i) => {
+            let name = &target[last + i + 1..];
+            (&target[..last + i], Some(name).filter(|n| !n.is_empty()))
+        }
+        None => (target, None),
+    }
+}
+
+fn flag_names(flags: u16) -> String {
+    let mut names = Vec::new();
+    if flags & 0x01 != 0 {
+        names.push("in use");
+    }
+    if flags & 0x02 != 0 {
+        names.push("directory");
+    }
+    names.join(", ")
+}
+
+// Header, decoded $STANDARD_INFORMATION and $FILE_NAME values, then every attribute header
+fn stat(parser: &mut Parser, entry_id: u64) -> Result<Vec<String>, Failure> {
+    let entry = parser
+        .entry(entry_id)
+        .map_err(fatal(&format!("Entry {}", entry_id)))?;
+    let header = &entry.header;
+    let mut lines = vec![
+        format!("Entry: {}", entry_id),
+        format!("Sequence: {}", header.sequence_number),
+        format!("Status: {}", header.status()),
+        format!(
+            "Flags: {:#06x} ({})",
+            header.flags,
+            flag_names(header.flags)
+        ),
+        format!("Links: {}", header.link_count),
+        format!("LSN: {}", header.log_sequence_number),
+        format!(
+            "Base record: {}-{}",
+            header.base_mft_record.entry, header.base_mft_record.sequence
+        ),
+        format!(
+            "Size: {} of {} bytes used",
+            header.used_entry_size, header.total_entry_size
+        ),
+    ];
+    let record = parser
+        .block_at(entry_id)
+        .and_then(|block| Record::from(parser, &block));
+    match record {
+        Ok(record) => {
+            lines.insert(1, format!("Path: {}", record.path.display()));
+            lines.push(String::new());
+            lines.push(String::from("$STANDARD_INFORMATION:"));
+            lines.push(format!("  Attributes: {:#010x}", record.file_attributes));
+            lines.push(format!("  Owner ID: {}", record.owner_id));
+            lines.push(format!("  Security ID: {}", record.security_id));
+            lines.push(format!("  USN: {}", record.usn));
+            lines.push(format!("  Created: {}", record.created.to_rfc3339()));
+            lines.push(format!("  Modified: {}", record.modified.to_rfc3339()));
+            lines.push(format!("  Accessed: {}", record.accessed.to_rfc3339()));
+            lines.push(format!("  Changed: {}", record.changed.to_rfc3339()));
+            for file_name in &record.file_names {
+                lines.push(String::new());
+                lines.push(format!(
+                    "$FILE_NAME: {} (namespace {}, parent {}-{})",
+                    file_name.name,
+                    file_name.name_space,
+                    file_name.parent_entry_id,
+                    file_name.parent_sequence_number
+                ));
+                lines.push(format!("  Created: {}", file_name.created.to_rfc3339()));
+                lines.push(format!("  Modified: {}", file_name.modified.to_rfc3339()));
+                lines.push(format!("  Accessed: {}", file_name.accessed.to_rfc3339()));
+                lines.push(format!("  Changed: {}", file_name.changed.to_rfc3339()));
+            }
+            if let Some(zone) = &record.zone_identifier {
+                lines.push(String::new());
+                lines.push(String::from("Zone.Identifier:"));
+                lines.extend(zone.lines().map(|l| format!("  {}", l)));
+            }
+            if !record.timestomp.is_empty() {
+                let reasons: Vec<String> = record.timestomp.iter().map(|r| r.to_string()).collect();
+                lines.push(String::new());
+                lines.push(format!("Timestomp: {}", reasons.join(", ")));
+            }
+        }
+        // The attribute headers below are still worth seeing for an entry that does not parse
+        Err(e) => {
+            lines.push(String::new());
+            lines.push(format!("Error: {}", e));
+        }
+    }
+    lines.push(String::new());
+    lines.push(String::from("Attributes:"));
+    for attribute in &entry.attributes {
+        let mut line = format!(
+            "  {:#x} {}, instance {}",
+            attribute.type_code,
+            Attribute::type_name(attribute.type_code),
+            attribute.instance
+        );
+        if let Some(name) = &attribute.name {
+            line.push_str(&format!(", name {}", name));
+        }
+        match &attribute.data {
+            AttributeData::Resident { data_size, .. } => {
+                line.push_str(&format!(", resident, {} bytes", data_size));
+            }
+            AttributeData::NonResident {
+                lowest_vcn,
+                highest_vcn,
+                allocated_size,
+                data_size,
+                ..
+            } => line.push_str(&format!(
+                ", non-resident, {} bytes ({} allocated), VCN {}-{}",
+                data_size, allocated_size, lowest_vcn, highest_vcn
+            )),
+        }
+        lines.push(line);
+    }
+    Ok(lines)
+}
@@ -0,0 +1,309 @@
+use clap::Parser as _;
+use log::LevelFilter;
+use mft::Iterator as MftIter;
+use mft::{ErrorKind, OutputType, Parser, ParserSettings};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+mod commands;
+
+const AFTER_HELP: &str = "\
+Include and exclude rules:
+  Regexes are matched ignoring case. An entry matching an exclude rule is skipped. With include
+  rules an entry has to match a path or a filename include rule.
+
+Filters:
+  Conditions on a field joined with and, or, not and parentheses. Numbers take 0x and KB to TB,
+  times are dates or RFC 3339, text is compared ignoring case and ~ matches a regex.
+  Operators: = != < <= > >= ~ in (a, b)
+  Fields: entry_id sequence_number flags link_count lsn deleted directory status
+          file_attributes owner_id security_id usn created modified accessed changed path
+          filename ext size allocated_size parent_entry_id fn_created fn_modified
+          fn_accessed fn_changed timestomped
+
+Exit codes:
+  0  Every entry was read and written, entries that could not be parsed are written as
+     diagnostic rows (csv, tsv and json) and counted in the error report
+  1  The input, output or error report file could not be used, or the entry, path or stream
+     given to a subcommand does not exist
+  2  Invalid arguments
+  3  --fail-fast was set and an entry could not be parsed";
+
+/// Parses an NTFS $MFT and writes a row for every entry with its full path
+#[derive(Debug, clap::Parser)]
+#[command(
+    name = "mft-cli",
+    version,
+    after_help = AFTER_HELP,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<commands::Command>,
+
+    /// $MFT file to parse, - reads it from stdin
+    #[arg(required = true)]
+    input: Option<PathBuf>,
+
+    /// Output format: csv, tsv, json, bodyfile, l2t_csv or timeline_json
+    #[arg(short, long, default_value = "csv", value_parser = parse_output_type)]
+    format: OutputType,
+
+    /// Write to a file instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Drive letter paths start with, paths start with {Root} without one
+    #[arg(short, long, value_name = "LETTER", value_parser = parse_drive)]
+    drive: Option<char>,
+
+    /// Only write entries with a path matching the regex, can be repeated
+    #[arg(long, value_name = "REGEX")]
+    include: Vec<String>,
+
+    /// Skip entries with a path matching the regex, can be repeated
+    #[arg(long, value_name = "REGEX")]
+    exclude: Vec<String>,
+
+    /// Only write entries with a filename matching the regex, can be repeated
+    #[arg(long, value_name = "REGEX")]
+    include_name: Vec<String>,
+
+    /// Skip entries with a filename matching the regex, can be repeated
+    #[arg(long, value_name = "REGEX")]
+    exclude_name: Vec<String>,
+
+    /// --include for every regex in a file, one per line, # starts a comment
+    #[arg(long, value_name = "FILE")]
+    include_from: Vec<PathBuf>,
+
+    /// --exclude for every regex in a file
+    #[arg(long, value_name = "FILE")]
+    exclude_from: Vec<PathBuf>,
+
+    /// --include-name for every regex in a file
+    #[arg(long, value_name = "FILE")]
+    include_name_from: Vec<PathBuf>,
+
+    /// --exclude-name for every regex in a file
+    #[arg(long, value_name = "FILE")]
+    exclude_name_from: Vec<PathBuf>,
+
+    /// Print how many entries each include and exclude rule matched to stderr
+    #[arg(long)]
+    rule_hits: bool,
+
+    /// Only write records matching the expression, e.g.
+    /// "modified > 2024-01-01 and ext in (exe, dll) and not deleted", can be repeated
+    #[arg(long, value_name = "EXPR")]
+    filter: Vec<String>,
+
+    /// Comma separated csv and tsv columns, e.g. entry_id,path,size. Defaults to
+    /// entry_id,path,is_file,is_deleted,filename,created,modified,accessed,timestomp,status,error
+    #[arg(long, value_name = "LIST")]
+    columns: Option<String>,
+
+    /// Scan the slack of each entry for leftover attributes
+    #[arg(long)]
+    slack: bool,
+
+    /// Read entries as they are written instead of scanning the whole input first
+    #[arg(long)]
+    streaming: bool,
+
+    /// Memory map the input instead of reading it through a buffer
+    #[arg(long)]
+    mmap: bool,
+
+    /// Worker threads used to decode entries
+    #[arg(short, long, default_value_t = 1, value_name = "N")]
+    threads: usize,
+
+    /// off, error, warn, info, debug or trace
+    #[arg(
+        short,
+        long,
+        global = true,
+        default_value = "warn",
+        value_name = "LEVEL"
+    )]
+    log_level: LevelFilter,
+
+    /// Stop at the first entry that cannot be parsed
+    #[arg(long)]
+    fail_fast: bool,
+
+    /// Write the error report as JSON to a file
+    #[arg(long, value_name = "FILE")]
+    error_report: Option<PathBuf>,
+
+    /// Do not print the record and error summary to stderr
+    #[arg(short, long)]
+    quiet: bool,
+}
+
+fn parse_output_type(value: &str) -> Result<OutputType, String> {
+    value.parse().map_err(|e: mft::Error| e.to_string())
+}
+
+fn parse_drive(value: &str) -> Result<char, String> {
+    let value = value.trim_end_matches(':');
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(drive), None) if drive.is_ascii_alphabetic() => Ok(drive.to_ascii_uppercase()),
+        _ => Err(format!("{} is not a drive letter", value)),
+    }
+}
+
+// Reason the run stopped early, each maps to one of the documented exit codes
+enum Failure {
+    Usage(String),
+    Fatal(String),
+    FailFast(mft::Error),
+}
+
+impl Failure {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Failure::Fatal(_) => ExitCode::from(1),
+            Failure::Usage(_) => ExitCode::from(2),
+            Failure::FailFast(_) => ExitCode::from(3),
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Failure::Usage(error) | Failure::Fatal(error) => write!(f, "{}", error),
+            Failure::FailFast(error) => write!(f, "Stopped at an entry that failed: {}", error),
+        }
+    }
+}
+
+fn fatal<E: fmt::Display>(context: &str) -> impl FnOnce(E) -> Failure + '_ {
+    move |e| Failure::Fatal(format!("{}: {}", context, e))
+}
+
+fn settings(args: &Args) -> mft::Result<ParserSettings> {
+    let mut settings = ParserSettings::new()
+        .output_type(args.format)
+        .scan_slack(args.slack)
+        .streaming(args.streaming)
+        .mmap(args.mmap)
+        .threads(args.threads);
+    if let Some(drive) = args.drive {
+        settings = settings.drive_char(drive);
+    }
+    // Matched case insensitively, an entry matching any rule of a list matches the list
+    for regex in &args.include {
+        settings = settings.path_inclusion_regex(regex)?;
+    }
+    for regex in &args.exclude {
+        settings = settings.path_exclusion_regex(regex)?;
+    }
+    for regex in &args.include_name {
+        settings = settings.filename_inclusion_regex(regex)?;
+    }
+    for regex in &args.exclude_name {
+        settings = settings.filename_exclusion_regex(regex)?;
+    }
+    for path in &args.include_from {
+        settings = settings.path_inclusion_file(path)?;
+    }
+    for path in &args.exclude_from {
+        settings = settings.path_exclusion_file(path)?;
+    }
+    for path in &args.include_name_from {
+        settings = settings.filename_inclusion_file(path)?;
+    }
+    for path in &args.exclude_name_from {
+        settings = settings.filename_exclusion_file(path)?;
+    }
+    if !args.filter.is_empty() {
+        let filters: Vec<String> = args.filter.iter().map(|f| format!("({})", f)).collect();
+        settings = settings.filter(&filters.join(" and "))?;
+    }
+    if let Some(columns) = &args.columns {
+        settings = settings.columns(mft::csv::parse_columns(columns)?);
+    }
+    Ok(settings)
+}
+
+// Opens the input, - reads it from stdin
+fn open(input: &Path, settings: ParserSettings) -> Result<Parser, Failure> {
+    match input.to_str() {
+        Some("-") => Parser::from_reader(std::io::stdin().lock(), settings),
+        _ => Parser::with_settings(input, settings),
+    }
+    .map_err(fatal(&input.display().to_string()))
+}
+
+fn run(args: &Args, input: &Path) -> Result<(), Failure> {
+    let settings = settings(args).map_err(|e| Failure::Usage(e.to_string()))?;
+    let parser = open(input, settings)?;
+
+    let writer: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(BufWriter::new(
+            File::create(path).map_err(fatal(&path.display().to_string()))?,
+        )),
+        None => Box::new(BufWriter::new(std::io::stdout().lock())),
+    };
+    let mut iter = MftIter::from(parser).fail_fast(args.fail_fast);
+    let columns = iter.inner.settings.csv_columns();
+    let mut sink = iter.inner.settings.output_type.sink(writer, &columns);
+    let result = iter.write_to(sink.as_mut());
+
+    let errors = iter.inner.error_report();
+    if !args.quiet {
+        eprintln!("Records: {}", iter.inner.status_summary());
+        if args.rule_hits {
+            eprintln!("Rules:");
+            for rule in iter.inner.settings.rule_hits() {
+                eprintln!("  {}", rule);
+            }
+        }
+        if !errors.is_empty() {
+            eprintln!("Errors: {}", errors);
+        }
+    }
+    if let Some(path) = &args.error_report {
+        let file = File::create(path).map_err(fatal(&path.display().to_string()))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), &errors)
+            .map_err(fatal(&path.display().to_string()))?;
+    }
+    match result {
+        // Errors from the input or the output end the run whether failing fast or not
+        Err(e) if args.fail_fast && !matches!(e.kind(), ErrorKind::Reader | ErrorKind::Output) => {
+            Err(Failure::FailFast(e))
+        }
+        Err(e) => Err(Failure::Fatal(format!("Unable to write records: {}", e))),
+        Ok(()) => Ok(()),
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    simple_logger::SimpleLogger::new()
+        .with_level(args.log_level)
+        .env()
+        .init()
+        .ok();
+    let result = match (&args.command, &args.input) {
+        (Some(command), _) => command.run(),
+        (None, Some(input)) => run(&args, input),
+        // Clap requires the input without a subcommand
+        (None, None) => Err(Failure::Usage(String::from("No input given"))),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(failure) => {
+            eprintln!("mft-cli: {}", failure);
+            failure.exit_code()
+        }
+    }
+}
@@ -1,9 +1,55 @@
-use std::io::{Read, Seek};
+use std::io::{Cursor, Read, Seek};
+
+use crate::raw::RecordStatus;
 
 #[derive(Debug, Clone)]
 pub struct Block {
     pub blocks: Vec<SectionPointer>,
     pub entry_id: u64,
+    pub status: RecordStatus,
+    pub error: Option<crate::ErrorSample>, // Set when the entry could not be parsed
+    // Built from blocks on creation, call Block::reindex after changing blocks
+    index: AttributeIndex,
+}
+
+// Positions in Block::blocks sorted by type and by instance, both keep attribute order for ties
+#[derive(Debug, Clone, Default)]
+struct AttributeIndex {
+    by_type: Vec<(BlockType, u16)>,
+    by_instance: Vec<(u16, u16)>,
+}
+
+impl AttributeIndex {
+    fn new(blocks: &[SectionPointer]) -> Self {
+        let mut by_type: Vec<(BlockType, u16)> = blocks
+            .iter()
+            .enumerate()
+            .map(|(i, b)| (b.block_type, i as u16))
+            .collect();
+        by_type.sort();
+        let mut by_instance: Vec<(u16, u16)> = blocks
+            .iter()
+            .enumerate()
+            .filter_map(|(i, b)| b.attribute_id.map(|id| (id, i as u16)))
+            .collect();
+        by_instance.sort();
+        Self {
+            by_type,
+            by_instance,
+        }
+    }
+}
+
+// Block for an entry, blocks are pushed in entry order so this is a direct index unless the
+// scan skipped or repeated an entry, in which case it falls back to a binary search
+pub fn find_block(blocks: &[Block], entry_id: u64) -> Option<&Block> {
+    match blocks.get(entry_id as usize) {
+        Some(block) if block.entry_id == entry_id => Some(block),
+        _ => blocks
+            .binary_search_by_key(&entry_id, |b| b.entry_id)
+            .ok()
+            .map(|i| &blocks[i]),
+    }
 }
 
 impl Block {
@@ -19,6 +65,7 @@ impl Block {
             attribute_id: None,
             offset: entry.offset,
             size: entry.header.total_entry_size as u64,
+            allocated_size: entry.header.total_entry_size as u64,
         }];
 
         // Create Attributes Blocks
@@ -26,7 +73,7 @@ impl Block {
             use crate::raw::AttributeData;
             // Get offsets based on if the attribute is resident or not
             let data_offset = entry.offset
-                + attribute.offset as u64
+                + attribute.offset
                 + match attribute.data {
                     AttributeData::Resident {
                         data_offset: offset,
@@ -40,7 +87,11 @@ impl Block {
                 } => size as u64,
                 AttributeData::NonResident {
                     data_size: size, ..
-                } => size as u64,
+                } => size,
+            };
+            let allocated_size = match attribute.data {
+                AttributeData::Resident { .. } => data_size,
+                AttributeData::NonResident { allocated_size, .. } => allocated_size,
             };
             let is_resident = match attribute.data {
                 AttributeData::Resident { .. } => true,
@@ -57,32 +108,107 @@ impl Block {
                 attribute_id: Some(attribute.instance),
                 offset: data_offset,
                 size: data_size,
+                allocated_size,
             });
             // Zone Identifier checks
             if BlockType::from_attribute_type_code(attribute.type_code) == BlockType::Data {
                 if let Some(name) = &attribute.name {
                     if name == "Zone.Identifier" {
-                        trace!(
-                            "Creating ZoneIdentifier SectionPointer for record {}",
-                            record_n
-                        );
-                        blocks.push(SectionPointer {
-                            block_type: BlockType::ZoneIdentifier,
-                            is_resident,
-                            attribute_id: None,
-                            offset: data_offset,
-                            size: data_size,
-                        });
+                        // Resident content is read whole, so it has to fit in the attribute
+                        let fits = match attribute.data {
+                            AttributeData::Resident { data_offset, .. } => {
+                                data_offset as u64 + data_size <= attribute.record_len as u64
+                            }
+                            AttributeData::NonResident { .. } => true,
+                        };
+                        if fits {
+                            trace!(
+                                "Creating ZoneIdentifier SectionPointer for record {}",
+                                record_n
+                            );
+                            blocks.push(SectionPointer {
+                                block_type: BlockType::ZoneIdentifier,
+                                is_resident,
+                                attribute_id: None,
+                                offset: data_offset,
+                                size: data_size,
+                                allocated_size,
+                            });
+                        } else {
+                            debug!(
+                                "Skipping ZoneIdentifier of record {}, {} bytes do not fit in the attribute",
+                                record_n, data_size
+                            );
+                        }
                     }
                 }
             }
         }
         //
         Ok(Self {
+            index: AttributeIndex::new(&blocks),
             blocks,
             entry_id: record_n,
+            status: entry.header.status(),
+            error: None,
         })
     }
+
+    // Placeholder for an entry that failed to parse, keeps the entry offset for diagnostics
+    pub fn new_with_error(
+        offset: u64,
+        record_n: u64,
+        status: RecordStatus,
+        error: &crate::Error,
+    ) -> Self {
+        let blocks = vec![SectionPointer {
+            block_type: BlockType::Entry,
+            is_resident: true,
+            attribute_id: None,
+            offset,
+            size: crate::MFT_RECORD_SIZE,
+            allocated_size: crate::MFT_RECORD_SIZE,
+        }];
+        Self {
+            index: AttributeIndex::new(&blocks),
+            blocks,
+            entry_id: record_n,
+            status,
+            error: Some(error.sample(record_n)),
+        }
+    }
+
+    pub fn reindex(&mut self) {
+        self.index = AttributeIndex::new(&self.blocks);
+    }
+
+    // Every pointer of a type, in attribute order
+    pub fn pointers(
+        &self,
+        block_type: BlockType,
+    ) -> impl std::iter::Iterator<Item = &SectionPointer> {
+        let by_type = &self.index.by_type;
+        let start = by_type.partition_point(|(t, _)| *t < block_type);
+        let end = by_type.partition_point(|(t, _)| *t <= block_type);
+        by_type[start..end]
+            .iter()
+            .map(move |(_, i)| &self.blocks[*i as usize])
+    }
+
+    // First pointer of a type, e.g. the unnamed $DATA stream which sorts before named ones
+    pub fn pointer(&self, block_type: BlockType) -> Option<&SectionPointer> {
+        self.pointers(block_type).next()
+    }
+
+    // Pointer for an attribute instance, as referenced by attribute lists
+    pub fn by_instance(&self, instance: u16) -> Option<&SectionPointer> {
+        let by_instance = &self.index.by_instance;
+        let start = by_instance.partition_point(|(id, _)| *id < instance);
+        by_instance
+            .get(start)
+            .filter(|(id, _)| *id == instance)
+            .map(|(_, i)| &self.blocks[*i as usize])
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -92,9 +218,20 @@ pub struct SectionPointer {
     pub attribute_id: Option<u16>,
     pub offset: u64,
     pub size: u64,
+    // Clusters allocated for non-resident data, the same as size when resident
+    pub allocated_size: u64,
+}
+
+impl SectionPointer {
+    // Reader at the pointer within the bytes of the entry it belongs to, read from entry_offset
+    pub fn reader<'a>(&self, entry_bytes: &'a [u8], entry_offset: u64) -> Cursor<&'a [u8]> {
+        let mut reader = Cursor::new(entry_bytes);
+        reader.set_position(self.offset.saturating_sub(entry_offset));
+        reader
+    }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
 pub enum BlockType {
     // Top Level
     Entry,
@@ -145,3 +282,42 @@ impl BlockType {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, BASE_TIME};
+    use std::io::Cursor;
+
+    fn block(entry: u32) -> Block {
+        let record = fixtures::file(entry, 5, "a.txt", b"hello", BASE_TIME);
+        let mut reader = Cursor::new(record);
+        let entry = crate::raw::Entry::from_reader_at(&mut reader, 0, entry as u64).unwrap();
+        Block::new_with_entry(&mut reader, &entry, entry.entry_n).unwrap()
+    }
+
+    #[test]
+    fn attribute_index_test() {
+        let block = block(16);
+        assert_eq!(block.pointers(BlockType::FileName).count(), 1);
+        assert_eq!(block.pointer(BlockType::Data).unwrap().size, 5);
+        assert!(block.pointer(BlockType::AttributeList).is_none());
+        // Instances are assigned in attribute order by the fixture builder
+        assert_eq!(
+            block.by_instance(1).unwrap().block_type,
+            BlockType::FileName
+        );
+        assert!(block.by_instance(7).is_none());
+    }
+
+    #[test]
+    fn find_block_test() {
+        let blocks: Vec<Block> = (0..4).map(block).collect();
+        assert_eq!(find_block(&blocks, 2).unwrap().entry_id, 2);
+        assert!(find_block(&blocks, 4).is_none());
+        // Not in entry order from index 0, falls back to searching
+        let blocks: Vec<Block> = (3..8).map(block).collect();
+        assert_eq!(find_block(&blocks, 5).unwrap().entry_id, 5);
+        assert!(find_block(&blocks, 1).is_none());
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::sync::{PoisonError, RwLock};
+
+// Entry ID and (Path Part, Parent Entry), None when the entry has no usable $FILE_NAME
+pub type PathPart = Option<(String, u64)>;
+
+// Path parts resolved so far, shared between worker threads so each part is only read once
+#[derive(Debug, Default)]
+pub struct PathCache {
+    parts: RwLock<Generations>,
+    // Unbounded when None
+    capacity: Option<usize>,
+}
+
+// When the current generation is full it replaces the previous one, which is dropped. Parts used
+// since the last swap stay cached and memory is capped at twice the capacity.
+#[derive(Debug, Default)]
+struct Generations {
+    current: HashMap<u64, PathPart>,
+    previous: HashMap<u64, PathPart>,
+}
+
+impl PathCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bounded(capacity: usize) -> Self {
+        Self {
+            parts: RwLock::default(),
+            capacity: Some(capacity.max(1)),
+        }
+    }
+
+    pub fn get(&self, entry_id: u64) -> Option<PathPart> {
+        // A poisoned lock still holds complete entries, inserts are a single operation
+        let parts = self.parts.read().unwrap_or_else(PoisonError::into_inner);
+        parts
+            .current
+            .get(&entry_id)
+            .or_else(|| parts.previous.get(&entry_id))
+            .cloned()
+    }
+
+    pub fn insert(&self, entry_id: u64, part: PathPart) {
+        let mut parts = self.parts.write().unwrap_or_else(PoisonError::into_inner);
+        if let Some(capacity) = self.capacity {
+            if parts.current.len() >= capacity {
+                parts.previous = std::mem::take(&mut parts.current);
+            }
+        }
+        parts.current.insert(entry_id, part);
+    }
+
+    pub fn len(&self) -> usize {
+        let parts = self.parts.read().unwrap_or_else(PoisonError::into_inner);
+        parts.current.len() + parts.previous.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounded_cache_test() {
+        let cache = PathCache::bounded(2);
+        for entry_id in 0..5 {
+            cache.insert(entry_id, Some((entry_id.to_string(), 5)));
+        }
+        assert!(cache.len() <= 4);
+        assert_eq!(cache.get(4), Some(Some((String::from("4"), 5))));
+        assert_eq!(cache.get(0), None);
+    }
+}
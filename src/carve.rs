@@ -0,0 +1,294 @@
+use chrono::{DateTime, Utc};
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+use crate::attributes::{FileName, StandardInformation};
+use crate::raw::{AttributeData, Entry, Header};
+use crate::Parser;
+
+// Records are always written sector aligned
+const SECTOR_SIZE: u64 = 512;
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+// A FILE (or BAAD) record recovered from outside of a contiguous MFT
+#[derive(Debug, Clone, Serialize)]
+pub struct CarvedRecord {
+    pub source_offset: u64,
+    pub signature: String,
+    pub entry_id: Option<u64>,
+    pub sequence_number: u16,
+    pub is_deleted: bool,
+    pub is_directory: bool,
+    pub fixup_valid: bool,
+    pub filename: Option<String>,
+    pub parent_entry_id: Option<u64>,
+    pub parent_sequence_number: Option<u16>,
+    // Only resolved when a reference MFT is supplied
+    pub path: Option<PathBuf>,
+    //
+    pub created: Option<DateTime<Utc>>,
+    pub modified: Option<DateTime<Utc>>,
+    pub accessed: Option<DateTime<Utc>>,
+}
+
+impl CarvedRecord {
+    pub fn from_entry_bytes(entry_bytes: &[u8], source_offset: u64) -> crate::Result<Self> {
+        let mut buffer = entry_bytes.to_vec();
+        let header = Header::from_reader(&mut Cursor::new(&buffer))?;
+        let fixup_valid = Entry::apply_fixups(&mut buffer, &header);
+        let entry = Entry::from_entry_bytes(
+            &buffer,
+            source_offset,
+            header.entry_number().unwrap_or_default(),
+        )?;
+        //
+        let mut standard_info = None;
+        let mut best_filename: Option<FileName> = None;
+        for attribute in &entry.attributes {
+            let (data_size, data_offset) = match attribute.data {
+                AttributeData::Resident {
+                    data_size,
+                    data_offset,
+                    ..
+                } => (data_size as usize, data_offset as usize),
+                AttributeData::NonResident { .. } => continue,
+            };
+            let start = attribute.offset as usize + data_offset;
+            let data = match buffer.get(start..start + data_size) {
+                Some(data) => data,
+                None => continue,
+            };
+            match attribute.type_code {
+                0x10 => {
+                    standard_info = StandardInformation::from_reader(&mut Cursor::new(data)).ok()
+                }
+                0x30 => {
+                    if let Ok(filename) = FileName::from_reader(&mut Cursor::new(data)) {
+                        // Prefer anything over the DOS (8.3) namespace
+                        let is_better = match &best_filename {
+                            Some(best) => best.name_space == 2 && filename.name_space != 2,
+                            None => true,
+                        };
+                        if is_better {
+                            best_filename = Some(filename);
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+        //
+        Ok(Self {
+            source_offset,
+            signature: String::from_utf8_lossy(&header.sig).to_string(),
+            entry_id: header.entry_number(),
+            sequence_number: header.sequence_number,
+            is_deleted: header.flags & 0x01 == 0,
+            is_directory: header.flags & 0x02 != 0,
+            fixup_valid,
+            parent_entry_id: best_filename
+                .as_ref()
+                .map(|f| f.parent_file_reference.entry),
+            parent_sequence_number: best_filename
+                .as_ref()
+                .map(|f| f.parent_file_reference.sequence),
+            filename: best_filename.map(|f| f.name),
+            path: None,
+            created: standard_info.as_ref().map(|s| s.creation_time),
+            modified: standard_info.as_ref().map(|s| s.modification_time),
+            accessed: standard_info.as_ref().map(|s| s.access_time),
+        })
+    }
+}
+
+// Scans any reader for FILE and BAAD signatures at sector alignment
+pub struct Carver<R: Read + Seek> {
+    reader: R,
+    size: u64,
+    next_offset: u64,
+    chunk: Vec<u8>,
+    chunk_offset: u64,
+    reference: Option<Parser>,
+}
+
+impl<R: Read + Seek> Carver<R> {
+    pub fn new(mut reader: R) -> crate::Result<Self> {
+        let size = reader.seek(SeekFrom::End(0))?;
+        trace!("Creating Carver over {} bytes", size);
+        Ok(Self {
+            reader,
+            size,
+            next_offset: 0,
+            chunk: Vec::new(),
+            chunk_offset: 0,
+            reference: None,
+        })
+    }
+
+    // Resolve carved record paths against an MFT from the same volume
+    pub fn with_reference(mut self, parser: Parser) -> Self {
+        self.reference = Some(parser);
+        self
+    }
+
+    fn fill_chunk(&mut self, offset: u64) -> crate::Result<()> {
+        self.reader.seek(SeekFrom::Start(offset))?;
+        self.chunk.clear();
+        (&mut self.reader)
+            .take(CHUNK_SIZE as u64)
+            .read_to_end(&mut self.chunk)
+            .map_err(|e| {
+                crate::Error::into_buffer_fill_error(e.into(), offset, CHUNK_SIZE as u64)
+            })?;
+        self.chunk_offset = offset;
+        Ok(())
+    }
+
+    fn carve_at(&mut self, offset: u64) -> crate::Result<Option<CarvedRecord>> {
+        self.reader.seek(SeekFrom::Start(offset))?;
+        let header = Header::from_reader(&mut self.reader)?;
+        if !header.is_plausible() {
+            return Ok(None);
+        }
+        let mut entry_bytes = vec![0; header.total_entry_size as usize];
+        self.reader.seek(SeekFrom::Start(offset))?;
+        self.reader.read_exact(&mut entry_bytes).map_err(|e| {
+            crate::Error::into_buffer_fill_error(e.into(), offset, entry_bytes.len() as u64)
+        })?;
+        let mut record = CarvedRecord::from_entry_bytes(&entry_bytes, offset)?;
+        if let (Some(parser), Some(parent_id), Some(filename)) = (
+            self.reference.as_mut(),
+            record.parent_entry_id,
+            record.filename.as_ref(),
+        ) {
+            // The parent entry may have been reused for another file since the record was written
+            let parent_sequence = record.parent_sequence_number.unwrap_or_default();
+            let same_parent = parser
+                .directory_tree()
+                .node(parent_id)
+                .map(|node| node.is_parent_of(parent_sequence));
+            record.path = match same_parent {
+                Some(false) => Some(PathBuf::from(format!("{{Orphaned}}/{}", filename))),
+                _ => match parser.get_file_path(parent_id) {
+                    Ok(parent) => Some(PathBuf::from(format!("{}/{}", parent.display(), filename))),
+                    Err(e) => {
+                        debug!(
+                            "Unable to resolve parent {} of carved record at {}: {}",
+                            parent_id, offset, e
+                        );
+                        None
+                    }
+                },
+            };
+        }
+        Ok(Some(record))
+    }
+}
+
+impl<R: Read + Seek> std::iter::Iterator for Carver<R> {
+    type Item = CarvedRecord;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.next_offset + 4 <= self.size {
+            let offset = self.next_offset;
+            let relative = offset.saturating_sub(self.chunk_offset) as usize;
+            if offset < self.chunk_offset || relative + 4 > self.chunk.len() {
+                match self.fill_chunk(offset) {
+                    Ok(()) if self.chunk.len() >= 4 => continue,
+                    Ok(()) => return None,
+                    Err(e) => {
+                        warn!("Carving stopped at offset {}: {}", offset, e);
+                        return None;
+                    }
+                }
+            }
+            self.next_offset += SECTOR_SIZE;
+            let sig = &self.chunk[relative..relative + 4];
+            if sig != b"FILE" && sig != b"BAAD" {
+                continue;
+            }
+            match self.carve_at(offset) {
+                Ok(Some(record)) => return Some(record),
+                Ok(None) => (),
+                Err(e) => debug!("Candidate record at offset {} rejected: {}", offset, e),
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw::tests::TEST_ENTRY;
+
+    #[test]
+    fn carve_test() {
+        // Two copies of a known record at sector aligned offsets, surrounded by noise and decoys
+        let mut blob: Vec<u8> = (0..16384).map(|i| (i * 7 % 253) as u8).collect();
+        blob[3072..4096].copy_from_slice(&TEST_ENTRY);
+        blob[9216..10240].copy_from_slice(&TEST_ENTRY);
+        blob[1024..1028].copy_from_slice(b"FILE"); // Aligned but not a sane header
+        blob[5000..5004].copy_from_slice(b"FILE"); // Not sector aligned
+        let records: Vec<CarvedRecord> = Carver::new(Cursor::new(blob)).unwrap().collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].source_offset, 3072);
+        assert_eq!(records[1].source_offset, 9216);
+        for record in records {
+            assert_eq!(record.signature, "FILE");
+            assert_eq!(record.entry_id, Some(0xa2a9));
+            assert_eq!(record.filename.as_deref(), Some("clrcompression.dll"));
+            assert!(record.fixup_valid);
+            assert!(record.created.is_some());
+            assert!(record.path.is_none());
+        }
+    }
+
+    #[test]
+    fn reference_paths() {
+        use crate::fixtures::{self, Times, BASE_TIME, FLAG_DIRECTORY, FLAG_IN_USE};
+        use crate::ParserSettings;
+        let times = Times::all(BASE_TIME);
+        // Directory 17 was deleted after its children were carved, freeing it bumped it to 2
+        let deleted_directory = fixtures::record(
+            17,
+            2,
+            FLAG_DIRECTORY,
+            &[
+                fixtures::standard_information(&times, 0x10),
+                fixtures::file_name(5, 1, "Old", 3, &times, 0),
+            ],
+        );
+        let mft = fixtures::volume(&[
+            fixtures::directory(16, 5, "Users", BASE_TIME),
+            deleted_directory,
+        ]);
+        let path = fixtures::write_temp("carve_reference", &mft);
+        let parser = Parser::with_settings(&path, ParserSettings::new().drive_char('C')).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let carved = |entry: u32, parent: u64, parent_sequence: u16, name: &str| {
+            fixtures::record(
+                entry,
+                1,
+                FLAG_IN_USE,
+                &[
+                    fixtures::standard_information(&times, 0x20),
+                    fixtures::file_name(parent, parent_sequence, name, 1, &times, 0),
+                ],
+            )
+        };
+        let blob = [
+            carved(40, 16, 1, "a.txt"),
+            carved(41, 16, 3, "b.txt"),
+            carved(42, 17, 1, "c.txt"),
+        ]
+        .concat();
+        let paths: Vec<PathBuf> = Carver::new(Cursor::new(blob))
+            .unwrap()
+            .with_reference(parser)
+            .map(|record| record.path.unwrap())
+            .collect();
+        let expected = ["C:/Users/a.txt", "{Orphaned}/b.txt", "C:/Old/c.txt"];
+        assert_eq!(paths, expected.map(PathBuf::from));
+    }
+}
@@ -0,0 +1,328 @@
+use chrono::{DateTime, Utc};
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::iter::Record;
+
+// Every Record field that can be written as a CSV column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Column {
+    EntryId,
+    SequenceNumber,
+    Path,
+    IsFile,
+    IsDeleted,
+    Filename,
+    Size,
+    FileReference,
+    NameSpace,
+    ParentEntryId,
+    ParentSequenceNumber,
+    AllocatedSize,
+    Flags,
+    LinkCount,
+    Lsn,
+    FileAttributes,
+    OwnerId,
+    SecurityId,
+    Usn,
+    Created,
+    Modified,
+    Accessed,
+    Changed,
+    CreatedRaw,
+    ModifiedRaw,
+    AccessedRaw,
+    ChangedRaw,
+    FnCreated,
+    FnModified,
+    FnAccessed,
+    FnChanged,
+    FnCreatedRaw,
+    FnModifiedRaw,
+    FnAccessedRaw,
+    FnChangedRaw,
+    FileNames,
+    ZoneIdentifier,
+    Timestomp,
+    Status,
+    Error,
+    Slack,
+}
+
+impl Column {
+    pub const ALL: [Column; 41] = [
+        Column::EntryId,
+        Column::SequenceNumber,
+        Column::Path,
+        Column::IsFile,
+        Column::IsDeleted,
+        Column::Filename,
+        Column::Size,
+        Column::FileReference,
+        Column::NameSpace,
+        Column::ParentEntryId,
+        Column::ParentSequenceNumber,
+        Column::AllocatedSize,
+        Column::Flags,
+        Column::LinkCount,
+        Column::Lsn,
+        Column::FileAttributes,
+        Column::OwnerId,
+        Column::SecurityId,
+        Column::Usn,
+        Column::Created,
+        Column::Modified,
+        Column::Accessed,
+        Column::Changed,
+        Column::CreatedRaw,
+        Column::ModifiedRaw,
+        Column::AccessedRaw,
+        Column::ChangedRaw,
+        Column::FnCreated,
+        Column::FnModified,
+        Column::FnAccessed,
+        Column::FnChanged,
+        Column::FnCreatedRaw,
+        Column::FnModifiedRaw,
+        Column::FnAccessedRaw,
+        Column::FnChangedRaw,
+        Column::FileNames,
+        Column::ZoneIdentifier,
+        Column::Timestomp,
+        Column::Status,
+        Column::Error,
+        Column::Slack,
+    ];
+
+    // Columns written when none are configured. The original layout stopped at accessed, the
+    // timestomp, status and error columns are new in the default header, --columns with the
+    // first eight gives the old one
+    pub const DEFAULT: [Column; 11] = [
+        Column::EntryId,
+        Column::Path,
+        Column::IsFile,
+        Column::IsDeleted,
+        Column::Filename,
+        Column::Created,
+        Column::Modified,
+        Column::Accessed,
+        Column::Timestomp,
+        Column::Status,
+        Column::Error,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Column::EntryId => "entry_id",
+            Column::SequenceNumber => "sequence_number",
+            Column::Path => "path",
+            Column::IsFile => "is_file",
+            Column::IsDeleted => "is_deleted",
+            Column::Filename => "filename",
+            Column::Size => "size",
+            Column::FileReference => "file_reference",
+            Column::NameSpace => "name_space",
+            Column::ParentEntryId => "parent_entry_id",
+            Column::ParentSequenceNumber => "parent_sequence_number",
+            Column::AllocatedSize => "allocated_size",
+            Column::Flags => "flags",
+            Column::LinkCount => "link_count",
+            Column::Lsn => "lsn",
+            Column::FileAttributes => "file_attributes",
+            Column::OwnerId => "owner_id",
+            Column::SecurityId => "security_id",
+            Column::Usn => "usn",
+            Column::Created => "created",
+            Column::Modified => "modified",
+            Column::Accessed => "accessed",
+            Column::Changed => "changed",
+            Column::CreatedRaw => "created_raw",
+            Column::ModifiedRaw => "modified_raw",
+            Column::AccessedRaw => "accessed_raw",
+            Column::ChangedRaw => "changed_raw",
+            Column::FnCreated => "fn_created",
+            Column::FnModified => "fn_modified",
+            Column::FnAccessed => "fn_accessed",
+            Column::FnChanged => "fn_changed",
+            Column::FnCreatedRaw => "fn_created_raw",
+            Column::FnModifiedRaw => "fn_modified_raw",
+            Column::FnAccessedRaw => "fn_accessed_raw",
+            Column::FnChangedRaw => "fn_changed_raw",
+            Column::FileNames => "file_names",
+            Column::ZoneIdentifier => "zone_identifier",
+            Column::Timestomp => "timestomp",
+            Column::Status => "status",
+            Column::Error => "error",
+            Column::Slack => "slack",
+        }
+    }
+
+    pub fn value(&self, record: &Record) -> String {
+        fn time(time: Option<DateTime<Utc>>) -> String {
+            time.map(|t| t.to_rfc3339()).unwrap_or_default()
+        }
+        fn raw(time: Option<u64>) -> String {
+            time.map(|t| t.to_string()).unwrap_or_default()
+        }
+        fn join<T: ToString>(values: &[T], separator: &str) -> String {
+            values
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<String>>()
+                .join(separator)
+        }
+        match self {
+            Column::EntryId => record.entry_id.to_string(),
+            Column::SequenceNumber => record.sequence_number.to_string(),
+            Column::Path => record.path.to_string_lossy().to_string(),
+            Column::IsFile => record.is_file.to_string(),
+            Column::IsDeleted => record.is_deleted.to_string(),
+            Column::Filename => record.filename.clone().unwrap_or_default(),
+            Column::Size => record.size.to_string(),
+            Column::FileReference => record.file_reference.to_string(),
+            Column::NameSpace => record.name_space.map(|n| n.to_string()).unwrap_or_default(),
+            Column::ParentEntryId => record
+                .parent_entry_id
+                .map(|n| n.to_string())
+                .unwrap_or_default(),
+            Column::ParentSequenceNumber => record
+                .parent_sequence_number
+                .map(|n| n.to_string())
+                .unwrap_or_default(),
+            Column::AllocatedSize => record.allocated_size.to_string(),
+            Column::Flags => record.flags.to_string(),
+            Column::LinkCount => record.link_count.to_string(),
+            Column::Lsn => record.lsn.to_string(),
+            Column::FileAttributes => record.file_attributes.to_string(),
+            Column::OwnerId => record.owner_id.to_string(),
+            Column::SecurityId => record.security_id.to_string(),
+            Column::Usn => record.usn.to_string(),
+            Column::Created => time(Some(record.created)),
+            Column::Modified => time(Some(record.modified)),
+            Column::Accessed => time(Some(record.accessed)),
+            Column::Changed => time(Some(record.changed)),
+            Column::CreatedRaw => record.created_raw.to_string(),
+            Column::ModifiedRaw => record.modified_raw.to_string(),
+            Column::AccessedRaw => record.accessed_raw.to_string(),
+            Column::ChangedRaw => record.changed_raw.to_string(),
+            Column::FnCreated => time(record.fn_created),
+            Column::FnModified => time(record.fn_modified),
+            Column::FnAccessed => time(record.fn_accessed),
+            Column::FnChanged => time(record.fn_changed),
+            Column::FnCreatedRaw => raw(record.fn_created_raw),
+            Column::FnModifiedRaw => raw(record.fn_modified_raw),
+            Column::FnAccessedRaw => raw(record.fn_accessed_raw),
+            Column::FnChangedRaw => raw(record.fn_changed_raw),
+            Column::FileNames => record
+                .file_names
+                .iter()
+                .map(|f| f.name.as_str())
+                .collect::<Vec<&str>>()
+                .join(";"),
+            Column::ZoneIdentifier => record.zone_identifier.clone().unwrap_or_default(),
+            Column::Timestomp => join(&record.timestomp, ";"),
+            Column::Status => record.status.to_string(),
+            Column::Error => record.error.clone().unwrap_or_default(),
+            Column::Slack => join(&record.slack, "; "),
+        }
+    }
+}
+
+impl fmt::Display for Column {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Column {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Column::ALL
+            .iter()
+            .find(|c| c.name() == s.trim())
+            .copied()
+            .ok_or_else(|| crate::Error::InvalidSetting(format!("Unknown column {}", s)))
+    }
+}
+
+// Parses a comma separated column list, e.g. "entry_id,path,size"
+pub fn parse_columns(columns: &str) -> crate::Result<Vec<Column>> {
+    columns.split(',').map(Column::from_str).collect()
+}
+
+// RFC 4180 writer, fields are only quoted when they need to be
+#[derive(Debug, Clone)]
+pub struct CsvWriter {
+    pub delimiter: char,
+    pub columns: Vec<Column>,
+}
+
+impl CsvWriter {
+    pub fn new(columns: Vec<Column>) -> Self {
+        Self {
+            delimiter: ',',
+            columns,
+        }
+    }
+
+    pub fn tsv(columns: Vec<Column>) -> Self {
+        Self {
+            delimiter: '\t',
+            columns,
+        }
+    }
+
+    pub fn escape<'a>(&self, field: &'a str) -> Cow<'a, str> {
+        if field.contains([self.delimiter, '"', '\n', '\r']) {
+            Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+        } else {
+            Cow::Borrowed(field)
+        }
+    }
+
+    fn line<I: Iterator<Item = String>>(&self, fields: I) -> String {
+        fields
+            .map(|f| self.escape(&f).to_string())
+            .collect::<Vec<String>>()
+            .join(&self.delimiter.to_string())
+    }
+
+    pub fn header(&self) -> String {
+        self.line(self.columns.iter().map(|c| c.name().to_string()))
+    }
+
+    pub fn row(&self, record: &Record) -> String {
+        self.line(self.columns.iter().map(|c| c.value(record)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_test() {
+        let writer = CsvWriter::new(Column::DEFAULT.to_vec());
+        assert_eq!(writer.escape("plain"), "plain");
+        assert_eq!(writer.escape("a,b"), "\"a,b\"");
+        assert_eq!(writer.escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(writer.escape("two\nlines"), "\"two\nlines\"");
+        let writer = CsvWriter::tsv(Column::DEFAULT.to_vec());
+        assert_eq!(writer.escape("a,b"), "a,b");
+        assert_eq!(writer.escape("a\tb"), "\"a\tb\"");
+    }
+
+    #[test]
+    fn columns_test() {
+        let columns = parse_columns("entry_id, path,size").unwrap();
+        assert_eq!(columns, vec![Column::EntryId, Column::Path, Column::Size]);
+        assert_eq!(CsvWriter::new(columns).header(), "entry_id,path,size");
+        assert!(parse_columns("entry_id,nope").is_err());
+        for column in Column::ALL {
+            assert_eq!(column.name().parse::<Column>().unwrap(), column);
+        }
+    }
+}
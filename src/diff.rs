@@ -0,0 +1,312 @@
+use chrono::{DateTime, Utc};
+use std::fmt;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::csv::CsvWriter;
+use crate::iter::Record;
+use crate::raw::RecordStatus;
+use crate::Parser;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Created,
+    Deleted,
+    // Same parent directory with a new name
+    Renamed,
+    // New parent directory, the name may have changed too
+    Moved,
+    // New $STANDARD_INFORMATION modified time or size, only for files
+    Modified,
+}
+
+impl ChangeKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChangeKind::Created => "created",
+            ChangeKind::Deleted => "deleted",
+            ChangeKind::Renamed => "renamed",
+            ChangeKind::Moved => "moved",
+            ChangeKind::Modified => "modified",
+        }
+    }
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub entry_id: u64,
+    // Of the file the change is about, an entry reused between the snapshots has a deleted and a
+    // created change with different sequence numbers
+    pub sequence_number: u16,
+    pub is_directory: bool,
+    // Path in the new snapshot, or in the old one for deleted files
+    pub path: PathBuf,
+    // Only set for renamed and moved files
+    pub old_path: Option<PathBuf>,
+    pub size: u64,
+    pub old_size: Option<u64>,
+    // When it happened according to the timestamps, deleting a file does not record a time
+    pub time: Option<DateTime<Utc>>,
+}
+
+impl Change {
+    fn new(kind: ChangeKind, record: &Record, time: Option<DateTime<Utc>>) -> Self {
+        Self {
+            kind,
+            entry_id: record.entry_id,
+            sequence_number: record.sequence_number,
+            is_directory: record.flags & 0x02 != 0,
+            path: record.path.clone(),
+            old_path: None,
+            size: record.size,
+            old_size: None,
+            time,
+        }
+    }
+
+    fn from_old(kind: ChangeKind, old: &Record, new: &Record, time: DateTime<Utc>) -> Self {
+        Self {
+            old_path: Some(old.path.clone()),
+            old_size: Some(old.size),
+            ..Self::new(kind, new, Some(time))
+        }
+    }
+}
+
+// An entry in one snapshot, entries that could not be read are left out of the diff
+enum Snapshot {
+    InUse(Box<Record>),
+    // Deleted, never used or only an extension of another entry
+    Free,
+    Unreadable,
+}
+
+impl Snapshot {
+    fn read(parser: &mut Parser, entry_id: u64) -> Self {
+        if entry_id >= parser.block_count() {
+            return Snapshot::Free;
+        }
+        let block = match parser.block_at(entry_id) {
+            Ok(block) => block,
+            Err(_) => return Snapshot::Unreadable,
+        };
+        match block.status {
+            RecordStatus::Valid => {}
+            RecordStatus::Zeroed => return Snapshot::Free,
+            RecordStatus::Baad | RecordStatus::Garbage => return Snapshot::Unreadable,
+        }
+        match Record::from(parser, &block) {
+            Ok(record) if record.is_deleted || record.filename.is_none() => Snapshot::Free,
+            Ok(record) => Snapshot::InUse(Box::new(record)),
+            Err(_) => Snapshot::Unreadable,
+        }
+    }
+}
+
+// Files changed between two snapshots of the same volume, compared entry by entry
+#[derive(Debug, Default, Clone)]
+pub struct Diff {
+    pub changes: Vec<Change>,
+    // Entries that could not be read in either snapshot
+    pub skipped: u64,
+}
+
+impl Diff {
+    pub fn new(old: &mut Parser, new: &mut Parser) -> Self {
+        let mut diff = Self::default();
+        for entry_id in 0..old.block_count().max(new.block_count()) {
+            match (Snapshot::read(old, entry_id), Snapshot::read(new, entry_id)) {
+                (Snapshot::Unreadable, _) | (_, Snapshot::Unreadable) => diff.skipped += 1,
+                (Snapshot::Free, Snapshot::Free) => {}
+                (Snapshot::Free, Snapshot::InUse(new)) => {
+                    diff.changes
+                        .push(Change::new(ChangeKind::Created, &new, Some(new.created)));
+                }
+                (Snapshot::InUse(old), Snapshot::Free) => {
+                    diff.changes
+                        .push(Change::new(ChangeKind::Deleted, &old, None));
+                }
+                // NTFS bumps the sequence number when an entry is freed, so a new one means the
+                // old file was deleted and the entry reused
+                (Snapshot::InUse(old), Snapshot::InUse(new))
+                    if old.sequence_number != new.sequence_number =>
+                {
+                    diff.changes
+                        .push(Change::new(ChangeKind::Deleted, &old, None));
+                    diff.changes
+                        .push(Change::new(ChangeKind::Created, &new, Some(new.created)));
+                }
+                (Snapshot::InUse(old), Snapshot::InUse(new)) => diff.compare(&old, &new),
+            }
+        }
+        diff
+    }
+
+    // Changes to a file that is in use in both snapshots
+    fn compare(&mut self, old: &Record, new: &Record) {
+        // Paths change when a parent directory is renamed, so the parent and name are compared
+        let kind = if old.parent_entry_id != new.parent_entry_id {
+            Some(ChangeKind::Moved)
+        } else if old.filename != new.filename {
+            Some(ChangeKind::Renamed)
+        } else {
+            None
+        };
+        if let Some(kind) = kind {
+            self.changes
+                .push(Change::from_old(kind, old, new, new.changed));
+        }
+        // Directory modified times change with every child, that is already in the list
+        if new.flags & 0x02 == 0 && (old.modified != new.modified || old.size != new.size) {
+            self.changes.push(Change::from_old(
+                ChangeKind::Modified,
+                old,
+                new,
+                new.modified,
+            ));
+        }
+    }
+
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> crate::Result<()> {
+        // Only used for quoting, the columns are the Change fields
+        let csv = CsvWriter::new(Vec::new());
+        writeln!(
+            writer,
+            "kind,entry_id,sequence_number,is_directory,path,old_path,size,old_size,time"
+        )?;
+        for change in &self.changes {
+            let path = |path: &PathBuf| csv.escape(&path.to_string_lossy()).to_string();
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{},{}",
+                change.kind,
+                change.entry_id,
+                change.sequence_number,
+                change.is_directory,
+                path(&change.path),
+                change.old_path.as_ref().map(path).unwrap_or_default(),
+                change.size,
+                change.old_size.map(|s| s.to_string()).unwrap_or_default(),
+                change.time.map(|t| t.to_rfc3339()).unwrap_or_default(),
+            )?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    // One JSON object per line, like OutputType::Json
+    pub fn write_json<W: Write>(&self, mut writer: W) -> crate::Result<()> {
+        for change in &self.changes {
+            serde_json::to_writer(&mut writer, change).map_err(crate::Error::output)?;
+            writeln!(writer)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, Times, BASE_TIME, FLAG_IN_USE, SECOND};
+    use crate::ParserSettings;
+
+    fn file(entry: u32, sequence: u16, flags: u16, parent: u64, name: &str, time: u64) -> Vec<u8> {
+        let times = Times::all(time);
+        fixtures::record(
+            entry,
+            sequence,
+            flags,
+            &[
+                fixtures::standard_information(&times, 0x20),
+                fixtures::file_name(parent, 1, name, 1, &times, 5),
+                fixtures::data(None, b"hello"),
+            ],
+        )
+    }
+
+    fn parser(name: &str, entries: &[Vec<u8>]) -> Parser {
+        let path = fixtures::write_temp(name, &fixtures::volume(entries));
+        let parser = Parser::with_settings(&path, ParserSettings::new().drive_char('C')).unwrap();
+        std::fs::remove_file(path).unwrap();
+        parser
+    }
+
+    #[test]
+    fn snapshot_changes() {
+        let later = BASE_TIME + 60 * SECOND;
+        let mut old = parser(
+            "diff_old",
+            &[
+                fixtures::directory(16, 5, "Users", BASE_TIME),
+                file(17, 1, FLAG_IN_USE, 16, "a.txt", BASE_TIME),
+                file(18, 1, FLAG_IN_USE, 5, "b.txt", BASE_TIME),
+                file(19, 1, FLAG_IN_USE, 5, "c.txt", BASE_TIME),
+                file(20, 1, FLAG_IN_USE, 5, "old.txt", BASE_TIME),
+                file(21, 1, FLAG_IN_USE, 5, "tmp.txt", BASE_TIME),
+            ],
+        );
+        let mut new = parser(
+            "diff_new",
+            &[
+                fixtures::directory(16, 5, "Users", later),
+                file(17, 1, FLAG_IN_USE, 16, "a2.txt", later),
+                file(18, 1, FLAG_IN_USE, 16, "b.txt", BASE_TIME),
+                file(19, 1, FLAG_IN_USE, 5, "c.txt", BASE_TIME),
+                file(20, 2, 0, 5, "old.txt", BASE_TIME),
+                file(21, 2, FLAG_IN_USE, 5, "new.txt", later),
+                file(22, 1, FLAG_IN_USE, 5, "x,y.txt", later),
+            ],
+        );
+        let diff = Diff::new(&mut old, &mut new);
+        assert_eq!(diff.skipped, 0);
+        let changes: Vec<(ChangeKind, u64, u16, String)> = diff
+            .changes
+            .iter()
+            .map(|c| {
+                let path = c.path.display().to_string();
+                (c.kind, c.entry_id, c.sequence_number, path)
+            })
+            .collect();
+        let expected = [
+            (ChangeKind::Renamed, 17, 1, "C:/Users/a2.txt"),
+            (ChangeKind::Modified, 17, 1, "C:/Users/a2.txt"),
+            (ChangeKind::Moved, 18, 1, "C:/Users/b.txt"),
+            (ChangeKind::Deleted, 20, 1, "C:/old.txt"),
+            (ChangeKind::Deleted, 21, 1, "C:/tmp.txt"),
+            (ChangeKind::Created, 21, 2, "C:/new.txt"),
+            (ChangeKind::Created, 22, 1, "C:/x,y.txt"),
+        ]
+        .map(|(kind, entry_id, sequence, path)| (kind, entry_id, sequence, path.to_string()));
+        assert_eq!(changes, expected);
+        assert_eq!(
+            diff.changes[0].old_path,
+            Some(PathBuf::from("C:/Users/a.txt"))
+        );
+        assert_eq!(diff.changes[3].time, None);
+
+        let mut csv = Vec::new();
+        diff.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 8);
+        assert!(lines[3].starts_with("moved,18,1,false,C:/Users/b.txt,C:/b.txt,5,5,"));
+        assert_eq!(lines[4], "deleted,20,1,false,C:/old.txt,,5,,");
+        assert!(lines[7].starts_with("created,22,1,false,\"C:/x,y.txt\",,5,,"));
+
+        let mut json = Vec::new();
+        diff.write_json(&mut json).unwrap();
+        let first: serde_json::Value =
+            serde_json::from_str(String::from_utf8(json).unwrap().lines().next().unwrap()).unwrap();
+        assert_eq!(first["kind"], "renamed");
+        assert_eq!(first["old_path"], "C:/Users/a.txt");
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::error::Error as ErrTrait;
 use std::fmt;
 use std::io::Error as IoError;
@@ -5,34 +6,231 @@ use std::io::Error as IoError;
 #[derive(Debug)]
 pub enum Error {
     Reader(IoError),
-    ValueRead(String),
-    BufferFill(String),
+    // Records could not be written or serialized
+    Output(IoError),
+    // A value could not be read, usually a truncated or corrupt record
+    ValueRead {
+        value_type: String,
+        reason: String,
+        context: ErrorContext,
+    },
+    BufferFill {
+        size: u64,
+        reason: String,
+        context: ErrorContext,
+    },
     MissingBlock(String, u64),
     MissingFileNameAttribute,
+    InvalidSetting(String),
+    // Something went wrong in the parser itself rather than in the input
+    Internal(String),
+    // An error that was only kept as a sample, e.g. for an entry that could not be read as a block
+    Reported(ErrorSample),
+}
+
+// Where an error happened, parts that are not known are None. The offset is absolute within the
+// input once the error has left the entry it was read from (see Error::in_entry)
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct ErrorContext {
+    pub entry_id: Option<u64>,
+    pub offset: Option<u64>,
+    pub attribute_type: Option<u32>,
+    pub field: Option<String>,
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(entry_id) = self.entry_id {
+            parts.push(format!("entry {}", entry_id));
+        }
+        if let Some(offset) = self.offset {
+            parts.push(format!("offset {:#x}", offset));
+        }
+        if let Some(attribute_type) = self.attribute_type {
+            parts.push(format!("attribute {:#x}", attribute_type));
+        }
+        if let Some(field) = &self.field {
+            parts.push(format!("field {}", field));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    Reader,
+    Output,
+    ValueRead,
+    BufferFill,
+    MissingBlock,
+    MissingFileNameAttribute,
+    InvalidSetting,
+    Internal,
+}
+
+impl ErrorKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorKind::Reader => "reader",
+            ErrorKind::Output => "output",
+            ErrorKind::ValueRead => "value_read",
+            ErrorKind::BufferFill => "buffer_fill",
+            ErrorKind::MissingBlock => "missing_block",
+            ErrorKind::MissingFileNameAttribute => "missing_file_name_attribute",
+            ErrorKind::InvalidSetting => "invalid_setting",
+            ErrorKind::Internal => "internal",
+        }
+    }
+
+    // Errors caused by the input rather than the parser or the environment
+    pub fn is_corruption(&self) -> bool {
+        matches!(
+            self,
+            ErrorKind::ValueRead
+                | ErrorKind::BufferFill
+                | ErrorKind::MissingBlock
+                | ErrorKind::MissingFileNameAttribute
+        )
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
 }
 
 impl Error {
     pub fn into_value_read_error(self, value_name: &str, value_type: &str) -> Self {
         match self {
-            Error::Reader(e) => Error::ValueRead(format!(
-                "Reader error occured trying to get value {} as {}. {}",
-                value_name, value_type, e
-            )),
-            _ => unreachable!(),
+            Error::Reader(e) => Error::ValueRead {
+                value_type: value_type.to_string(),
+                reason: e.to_string(),
+                context: ErrorContext {
+                    field: Some(value_name.to_string()),
+                    ..Default::default()
+                },
+            },
+            // Already more specific than a value read error
+            error => error,
         }
     }
     pub fn into_buffer_fill_error(self, offset: u64, size: u64) -> Self {
         match self {
-            Error::Reader(e) => Error::BufferFill(format!(
-                "Buffer could not be filled with {} at offset {}. {}",
-                size, offset, e
-            )),
-            _ => unreachable!(),
+            Error::Reader(e) => Error::BufferFill {
+                size,
+                reason: e.to_string(),
+                context: ErrorContext {
+                    offset: Some(offset),
+                    ..Default::default()
+                },
+            },
+            error => error,
         }
     }
+    // Writing JSON output failed, either in the writer or while serializing
+    pub fn output(error: serde_json::Error) -> Self {
+        Error::Output(error.into())
+    }
     pub fn missing_block(missing: &str, id: u64) -> Self {
         Error::MissingBlock(missing.to_string(), id)
     }
+    // A value that was read but makes no sense, e.g. an unknown form code
+    pub fn invalid_value(value_name: &str, value_type: &str, reason: String) -> Self {
+        Error::ValueRead {
+            value_type: value_type.to_string(),
+            reason,
+            context: ErrorContext {
+                field: Some(value_name.to_string()),
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Reader(_) => ErrorKind::Reader,
+            Error::Output(_) => ErrorKind::Output,
+            Error::ValueRead { .. } => ErrorKind::ValueRead,
+            Error::BufferFill { .. } => ErrorKind::BufferFill,
+            Error::MissingBlock(..) => ErrorKind::MissingBlock,
+            Error::MissingFileNameAttribute => ErrorKind::MissingFileNameAttribute,
+            Error::InvalidSetting(_) => ErrorKind::InvalidSetting,
+            Error::Internal(_) => ErrorKind::Internal,
+            Error::Reported(sample) => sample.kind,
+        }
+    }
+
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Error::ValueRead { context, .. } | Error::BufferFill { context, .. } => Some(context),
+            Error::Reported(sample) => Some(&sample.context),
+            _ => None,
+        }
+    }
+
+    fn context_mut(&mut self) -> Option<&mut ErrorContext> {
+        match self {
+            Error::ValueRead { context, .. } | Error::BufferFill { context, .. } => Some(context),
+            Error::Reported(sample) => Some(&mut sample.context),
+            _ => None,
+        }
+    }
+
+    // Position the failed read started at, relative to whatever the reader was reading
+    pub fn at_position(mut self, position: Option<u64>) -> Self {
+        if let Some(context) = self.context_mut() {
+            context.offset = context.offset.or(position);
+        }
+        self
+    }
+
+    // For errors read from a buffer that starts at base in the input, makes the offset absolute
+    pub fn relative_to(mut self, base: u64) -> Self {
+        if let Some(context) = self.context_mut() {
+            context.offset = context.offset.map(|offset| offset + base);
+        }
+        self
+    }
+
+    // For errors read from a buffer holding a single entry
+    pub fn in_entry(self, entry_id: u64, entry_offset: u64) -> Self {
+        self.relative_to(entry_offset).for_entry(entry_id)
+    }
+
+    // For errors read straight from the input, where the offset is already absolute
+    pub fn for_entry(mut self, entry_id: u64) -> Self {
+        if let Some(context) = self.context_mut() {
+            context.entry_id = context.entry_id.or(Some(entry_id));
+        }
+        self
+    }
+
+    pub fn in_attribute(mut self, type_code: u32) -> Self {
+        if let Some(context) = self.context_mut() {
+            context.attribute_type = context.attribute_type.or(Some(type_code));
+        }
+        self
+    }
+
+    // Owned summary of the error for reports, entry_id is used when the error does not know it
+    pub fn sample(&self, entry_id: u64) -> ErrorSample {
+        if let Error::Reported(sample) = self {
+            return sample.clone();
+        }
+        let mut context = self.context().cloned().unwrap_or_default();
+        if let Error::MissingBlock(_, id) = self {
+            context.entry_id = context.entry_id.or(Some(*id));
+        }
+        context.entry_id = context.entry_id.or(Some(entry_id));
+        ErrorSample {
+            kind: self.kind(),
+            message: self.to_string(),
+            context,
+        }
+    }
 }
 
 impl ErrTrait for Error {}
@@ -47,12 +245,129 @@ impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Error::Reader(error) => write!(f, "Reader error: {}", error),
-            Error::ValueRead(error) => write!(f, "Value read error: {}", error),
-            Error::BufferFill(error) => write!(f, "Buffer fill error: {}", error),
+            Error::Output(error) => write!(f, "Output error: {}", error),
+            Error::ValueRead {
+                value_type,
+                reason,
+                context,
+            } => write!(
+                f,
+                "Value read error: {} ({}). {}",
+                value_type, context, reason
+            ),
+            Error::BufferFill {
+                size,
+                reason,
+                context,
+            } => write!(
+                f,
+                "Buffer fill error: {} bytes ({}). {}",
+                size, context, reason
+            ),
             Error::MissingBlock(missing_block, id) => {
                 write!(f, "Missing {} block with id {}", missing_block, id)
             }
             Error::MissingFileNameAttribute => write!(f, "Missing file name attribute"),
+            Error::InvalidSetting(error) => write!(f, "Invalid setting: {}", error),
+            Error::Internal(error) => write!(f, "Internal error: {}", error),
+            Error::Reported(sample) => write!(f, "{}", sample.message),
+        }
+    }
+}
+
+// An error that has been reported, kept so the report does not hold on to readers or io errors
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ErrorSample {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub context: ErrorContext,
+}
+
+// Samples kept per error kind, the lowest entry ids win so the report does not depend on threads
+const SAMPLES_PER_KIND: usize = 5;
+
+// Errors seen during a run, counted by kind with a few sample entries for each
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct ErrorReport {
+    pub total: u64,
+    pub counts: BTreeMap<ErrorKind, u64>,
+    pub samples: BTreeMap<ErrorKind, Vec<ErrorSample>>,
+}
+
+impl ErrorReport {
+    pub fn add(&mut self, sample: ErrorSample) {
+        self.total += 1;
+        *self.counts.entry(sample.kind).or_default() += 1;
+        let samples = self.samples.entry(sample.kind).or_default();
+        let position = samples.partition_point(|s| s.context.entry_id <= sample.context.entry_id);
+        if position < SAMPLES_PER_KIND {
+            samples.insert(position, sample);
+            samples.truncate(SAMPLES_PER_KIND);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total == 0
+    }
+
+    // Number of errors caused by the input, the rest point at the parser or the environment
+    pub fn corruption_count(&self) -> u64 {
+        self.counts
+            .iter()
+            .filter(|(kind, _)| kind.is_corruption())
+            .map(|(_, count)| count)
+            .sum()
+    }
+}
+
+impl fmt::Display for ErrorReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} errors ({} from corrupt input)",
+            self.total,
+            self.corruption_count()
+        )?;
+        for (kind, count) in &self.counts {
+            write!(f, "\n  {}: {}", kind, count)?;
+            for sample in self.samples.get(kind).into_iter().flatten() {
+                write!(f, "\n    {}", sample.message)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_report_test() {
+        let mut report = ErrorReport::default();
+        for entry_id in (0..8).rev() {
+            let error = Error::from(IoError::from(std::io::ErrorKind::UnexpectedEof))
+                .into_value_read_error("flags", "read_u16")
+                .at_position(Some(0x16))
+                .in_entry(entry_id, entry_id * 1024)
+                .in_attribute(0x10);
+            report.add(error.sample(0));
         }
+        report.add(Error::missing_block("StandardInfo", 9).sample(9));
+        report.add(Error::Internal(String::from("worker panicked")).sample(10));
+        assert_eq!(report.total, 10);
+        assert_eq!(report.corruption_count(), 9);
+        assert_eq!(report.counts[&ErrorKind::ValueRead], 8);
+        let samples = &report.samples[&ErrorKind::ValueRead];
+        assert_eq!(samples.len(), SAMPLES_PER_KIND);
+        assert_eq!(samples[0].context.entry_id, Some(0));
+        assert_eq!(samples[1].context.offset, Some(1024 + 0x16));
+        assert_eq!(samples[1].context.attribute_type, Some(0x10));
+        assert_eq!(samples[1].context.field.as_deref(), Some("flags"));
+        assert!(samples[1]
+            .message
+            .contains("entry 1, offset 0x416, attribute 0x10, field flags"));
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["counts"]["missing_block"], 1);
     }
 }
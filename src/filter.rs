@@ -0,0 +1,796 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use regex::{Regex, RegexBuilder};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::attributes::StandardInformation;
+use crate::iter::Record;
+use crate::raw::Header;
+
+// Record fields a filter can match on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Field {
+    EntryId,
+    SequenceNumber,
+    Flags,
+    LinkCount,
+    Lsn,
+    Deleted,
+    Directory,
+    Status,
+    FileAttributes,
+    OwnerId,
+    SecurityId,
+    Usn,
+    Created,
+    Modified,
+    Accessed,
+    Changed,
+    Path,
+    Filename,
+    Ext,
+    Size,
+    AllocatedSize,
+    ParentEntryId,
+    FnCreated,
+    FnModified,
+    FnAccessed,
+    FnChanged,
+    Timestomped,
+}
+
+// Type of a field, literals in an expression are parsed as the type of the field they are compared to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    Number,
+    Time,
+    Text,
+    Bool,
+}
+
+impl Field {
+    pub const ALL: [Field; 27] = [
+        Field::EntryId,
+        Field::SequenceNumber,
+        Field::Flags,
+        Field::LinkCount,
+        Field::Lsn,
+        Field::Deleted,
+        Field::Directory,
+        Field::Status,
+        Field::FileAttributes,
+        Field::OwnerId,
+        Field::SecurityId,
+        Field::Usn,
+        Field::Created,
+        Field::Modified,
+        Field::Accessed,
+        Field::Changed,
+        Field::Path,
+        Field::Filename,
+        Field::Ext,
+        Field::Size,
+        Field::AllocatedSize,
+        Field::ParentEntryId,
+        Field::FnCreated,
+        Field::FnModified,
+        Field::FnAccessed,
+        Field::FnChanged,
+        Field::Timestomped,
+    ];
+
+    // Same names as the CSV columns where there is one
+    pub fn name(&self) -> &'static str {
+        match self {
+            Field::EntryId => "entry_id",
+            Field::SequenceNumber => "sequence_number",
+            Field::Flags => "flags",
+            Field::LinkCount => "link_count",
+            Field::Lsn => "lsn",
+            Field::Deleted => "deleted",
+            Field::Directory => "directory",
+            Field::Status => "status",
+            Field::FileAttributes => "file_attributes",
+            Field::OwnerId => "owner_id",
+            Field::SecurityId => "security_id",
+            Field::Usn => "usn",
+            Field::Created => "created",
+            Field::Modified => "modified",
+            Field::Accessed => "accessed",
+            Field::Changed => "changed",
+            Field::Path => "path",
+            Field::Filename => "filename",
+            Field::Ext => "ext",
+            Field::Size => "size",
+            Field::AllocatedSize => "allocated_size",
+            Field::ParentEntryId => "parent_entry_id",
+            Field::FnCreated => "fn_created",
+            Field::FnModified => "fn_modified",
+            Field::FnAccessed => "fn_accessed",
+            Field::FnChanged => "fn_changed",
+            Field::Timestomped => "timestomped",
+        }
+    }
+
+    pub fn field_type(&self) -> FieldType {
+        match self {
+            Field::Deleted | Field::Directory | Field::Timestomped => FieldType::Bool,
+            Field::Status | Field::Path | Field::Filename | Field::Ext => FieldType::Text,
+            Field::Created
+            | Field::Modified
+            | Field::Accessed
+            | Field::Changed
+            | Field::FnCreated
+            | Field::FnModified
+            | Field::FnAccessed
+            | Field::FnChanged => FieldType::Time,
+            _ => FieldType::Number,
+        }
+    }
+}
+
+impl FromStr for Field {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Field::ALL
+            .iter()
+            .find(|f| f.name().eq_ignore_ascii_case(s.trim()))
+            .copied()
+            .ok_or_else(|| crate::Error::InvalidSetting(format!("Unknown filter field {}", s)))
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub enum Value {
+    Number(u64),
+    Time(DateTime<Utc>),
+    Text(String),
+    Bool(bool),
+}
+
+impl Value {
+    // Parses a literal as the type of a field, numbers take 0x hex and KB, MB, GB and TB sizes
+    // and times are RFC 3339 or a UTC date and time
+    pub fn parse(field_type: FieldType, literal: &str) -> crate::Result<Self> {
+        let invalid = || {
+            crate::Error::InvalidSetting(format!(
+                "{} is not a valid {:?} value",
+                literal, field_type
+            ))
+        };
+        match field_type {
+            FieldType::Number => parse_number(literal).map(Value::Number).ok_or_else(invalid),
+            FieldType::Time => parse_time(literal).map(Value::Time).ok_or_else(invalid),
+            FieldType::Text => Ok(Value::Text(literal.to_lowercase())),
+            FieldType::Bool => match literal.to_lowercase().as_str() {
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                _ => Err(invalid()),
+            },
+        }
+    }
+
+    // Text is compared ignoring case
+    fn normalised(self) -> Self {
+        match self {
+            Value::Text(text) => Value::Text(text.to_lowercase()),
+            value => value,
+        }
+    }
+}
+
+fn parse_number(literal: &str) -> Option<u64> {
+    if let Some(hex) = literal
+        .strip_prefix("0x")
+        .or_else(|| literal.strip_prefix("0X"))
+    {
+        return u64::from_str_radix(hex, 16).ok();
+    }
+    let split = literal
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(literal.len());
+    let (number, unit) = literal.split_at(split);
+    let multiplier: u64 = match unit.to_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        "T" | "TB" => 1 << 40,
+        _ => return None,
+    };
+    number.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+fn parse_time(literal: &str) -> Option<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(literal) {
+        return Some(time.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(literal, format) {
+            return Some(time.and_utc());
+        }
+    }
+    NaiveDate::parse_from_str(literal, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|time| time.and_utc())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn holds(&self, ordering: Ordering) -> bool {
+        match self {
+            Op::Eq => ordering == Ordering::Equal,
+            Op::Ne => ordering != Ordering::Equal,
+            Op::Lt => ordering == Ordering::Less,
+            Op::Le => ordering != Ordering::Greater,
+            Op::Gt => ordering == Ordering::Greater,
+            Op::Ge => ordering != Ordering::Less,
+        }
+    }
+}
+
+// Source of field values, None for fields that are not known (yet)
+pub trait Fields {
+    fn value(&self, field: Field) -> Option<Value>;
+}
+
+impl Fields for Record {
+    fn value(&self, field: Field) -> Option<Value> {
+        let time = |time: Option<DateTime<Utc>>| time.map(Value::Time);
+        Some(match field {
+            Field::EntryId => Value::Number(self.entry_id),
+            Field::SequenceNumber => Value::Number(self.sequence_number as u64),
+            Field::Flags => Value::Number(self.flags as u64),
+            Field::LinkCount => Value::Number(self.link_count as u64),
+            Field::Lsn => Value::Number(self.lsn),
+            Field::Deleted => Value::Bool(self.flags & 0x01 == 0),
+            Field::Directory => Value::Bool(self.flags & 0x02 != 0),
+            Field::Status => Value::Text(self.status.to_string()),
+            Field::FileAttributes => Value::Number(self.file_attributes as u64),
+            Field::OwnerId => Value::Number(self.owner_id as u64),
+            Field::SecurityId => Value::Number(self.security_id as u64),
+            Field::Usn => Value::Number(self.usn),
+            Field::Created => Value::Time(self.created),
+            Field::Modified => Value::Time(self.modified),
+            Field::Accessed => Value::Time(self.accessed),
+            Field::Changed => Value::Time(self.changed),
+            Field::Path => Value::Text(self.path.to_string_lossy().to_string()),
+            Field::Filename => Value::Text(self.filename.clone()?),
+            Field::Ext => Value::Text(extension(self.filename.as_deref()?)),
+            Field::Size => Value::Number(self.size),
+            Field::AllocatedSize => Value::Number(self.allocated_size),
+            Field::ParentEntryId => Value::Number(self.parent_entry_id?),
+            Field::FnCreated => time(self.fn_created)?,
+            Field::FnModified => time(self.fn_modified)?,
+            Field::FnAccessed => time(self.fn_accessed)?,
+            Field::FnChanged => time(self.fn_changed)?,
+            Field::Timestomped => Value::Bool(!self.timestomp.is_empty()),
+        })
+    }
+}
+
+// Everything after the last dot, empty for names without one
+fn extension(filename: &str) -> String {
+    match filename.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => ext.to_string(),
+        _ => String::new(),
+    }
+}
+
+// Fields known from the record header and $STANDARD_INFORMATION, read before the path is resolved
+pub struct EntryFields<'a> {
+    pub entry_id: u64,
+    pub header: &'a Header,
+    pub standard_info: Option<&'a StandardInformation>,
+}
+
+impl Fields for EntryFields<'_> {
+    fn value(&self, field: Field) -> Option<Value> {
+        let header = self.header;
+        let standard_info = self.standard_info;
+        Some(match field {
+            Field::EntryId => Value::Number(self.entry_id),
+            Field::SequenceNumber => Value::Number(header.sequence_number as u64),
+            Field::Flags => Value::Number(header.flags as u64),
+            Field::LinkCount => Value::Number(header.link_count as u64),
+            Field::Lsn => Value::Number(header.log_sequence_number),
+            Field::Deleted => Value::Bool(header.flags & 0x01 == 0),
+            Field::Directory => Value::Bool(header.flags & 0x02 != 0),
+            Field::Status => Value::Text(header.status().to_string()),
+            Field::FileAttributes => Value::Number(standard_info?.file_attributes as u64),
+            Field::OwnerId => Value::Number(standard_info?.owner_id as u64),
+            Field::SecurityId => Value::Number(standard_info?.security_id as u64),
+            Field::Usn => Value::Number(standard_info?.update_sequence_number),
+            Field::Created => Value::Time(standard_info?.creation_time),
+            Field::Modified => Value::Time(standard_info?.modification_time),
+            Field::Accessed => Value::Time(standard_info?.access_time),
+            Field::Changed => Value::Time(standard_info?.mft_modification_time),
+            _ => return None,
+        })
+    }
+}
+
+// A boolean expression over record fields, built with the methods below or parsed from text, e.g.
+// modified > 2024-01-01 and ext in (exe, dll, ps1) and not deleted and size < 10MB
+#[derive(Debug, Clone)]
+pub enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Compare(Field, Op, Value),
+    In(Field, Vec<Value>),
+    // Case insensitive regex on a text field
+    Matches(Field, Regex),
+}
+
+impl Filter {
+    pub fn compare(field: Field, op: Op, value: Value) -> Self {
+        Filter::Compare(field, op, value.normalised())
+    }
+
+    pub fn one_of(field: Field, values: Vec<Value>) -> Self {
+        Filter::In(field, values.into_iter().map(Value::normalised).collect())
+    }
+
+    pub fn matches(field: Field, regex: &str) -> crate::Result<Self> {
+        let regex = RegexBuilder::new(regex)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| crate::Error::InvalidSetting(format!("Filter regex {}: {}", regex, e)))?;
+        Ok(Filter::Matches(field, regex))
+    }
+
+    // True for a boolean field
+    pub fn is(field: Field) -> Self {
+        Filter::Compare(field, Op::Eq, Value::Bool(true))
+    }
+
+    pub fn and(self, other: Filter) -> Self {
+        Filter::And(Box::new(self), Box::new(other))
+    }
+
+    pub fn or(self, other: Filter) -> Self {
+        Filter::Or(Box::new(self), Box::new(other))
+    }
+
+    // None when the outcome depends on fields that are not known, so a filter can be checked
+    // against the cheap fields first and only false skips the record
+    pub fn eval<F: Fields + ?Sized>(&self, fields: &F) -> Option<bool> {
+        match self {
+            Filter::And(left, right) => match left.eval(fields) {
+                Some(false) => Some(false),
+                left => match (left, right.eval(fields)) {
+                    (_, Some(false)) => Some(false),
+                    (Some(true), Some(true)) => Some(true),
+                    _ => None,
+                },
+            },
+            Filter::Or(left, right) => match left.eval(fields) {
+                Some(true) => Some(true),
+                left => match (left, right.eval(fields)) {
+                    (_, Some(true)) => Some(true),
+                    (Some(false), Some(false)) => Some(false),
+                    _ => None,
+                },
+            },
+            Filter::Not(filter) => filter.eval(fields).map(|matched| !matched),
+            Filter::Compare(field, op, value) => {
+                let ordering = fields.value(*field)?.normalised().partial_cmp(value)?;
+                Some(op.holds(ordering))
+            }
+            Filter::In(field, values) => {
+                let actual = fields.value(*field)?.normalised();
+                Some(values.contains(&actual))
+            }
+            Filter::Matches(field, regex) => match fields.value(*field)? {
+                Value::Text(text) => Some(regex.is_match(&text)),
+                _ => Some(false),
+            },
+        }
+    }
+
+    pub fn parse(expression: &str) -> crate::Result<Self> {
+        let tokens = tokenize(expression)?;
+        let mut parser = ExpressionParser {
+            tokens: &tokens,
+            position: 0,
+            length: expression.len(),
+        };
+        let filter = parser.or()?;
+        match parser.peek() {
+            None => Ok(filter),
+            Some(_) => Err(parser.error("Expected and, or or the end of the filter")),
+        }
+    }
+}
+
+impl std::ops::Not for Filter {
+    type Output = Filter;
+
+    fn not(self) -> Self::Output {
+        Filter::Not(Box::new(self))
+    }
+}
+
+impl FromStr for Filter {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Filter::parse(s)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    // Quoted values are never keywords and can hold spaces and operators
+    Quoted(String),
+    Op(&'static str),
+    Open,
+    Close,
+    Comma,
+}
+
+fn tokenize(expression: &str) -> crate::Result<Vec<(usize, Token)>> {
+    let mut tokens = Vec::new();
+    let mut chars = expression.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            ',' => Token::Comma,
+            '"' | '\'' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, q)) if q == c => break,
+                        Some((_, v)) => value.push(v),
+                        None => {
+                            return Err(crate::Error::InvalidSetting(format!(
+                                "Unterminated quote at position {} of the filter",
+                                start
+                            )))
+                        }
+                    }
+                }
+                Token::Quoted(value)
+            }
+            '<' | '>' | '=' | '!' | '~' => {
+                let followed_by_eq = chars.next_if(|(_, next)| *next == '=').is_some();
+                Token::Op(match (c, followed_by_eq) {
+                    ('<', false) => "<",
+                    ('<', true) => "<=",
+                    ('>', false) => ">",
+                    ('>', true) => ">=",
+                    ('=', _) => "=",
+                    ('!', true) => "!=",
+                    ('~', false) => "~",
+                    _ => {
+                        return Err(crate::Error::InvalidSetting(format!(
+                            "Unknown operator at position {} of the filter",
+                            start
+                        )))
+                    }
+                })
+            }
+            c => {
+                let mut value = String::from(c);
+                while let Some((_, next)) =
+                    chars.next_if(|(_, next)| !is_separator(*next) && !next.is_whitespace())
+                {
+                    value.push(next);
+                }
+                Token::Word(value)
+            }
+        };
+        tokens.push((start, token));
+    }
+    Ok(tokens)
+}
+
+fn is_separator(c: char) -> bool {
+    matches!(
+        c,
+        '(' | ')' | ',' | '"' | '\'' | '<' | '>' | '=' | '!' | '~'
+    )
+}
+
+// Recursive descent, or binds loosest, then and, then not
+struct ExpressionParser<'a> {
+    tokens: &'a [(usize, Token)],
+    position: usize,
+    length: usize,
+}
+
+impl ExpressionParser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(_, token)| token)
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.position).map(|(_, token)| token);
+        self.position += 1;
+        token
+    }
+
+    fn error(&self, reason: &str) -> crate::Error {
+        let offset = self
+            .tokens
+            .get(self.position)
+            .map(|(offset, _)| *offset)
+            .unwrap_or(self.length);
+        crate::Error::InvalidSetting(format!("{} at position {} of the filter", reason, offset))
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn or(&mut self) -> crate::Result<Filter> {
+        let mut filter = self.and()?;
+        while self.keyword("or") {
+            filter = filter.or(self.and()?);
+        }
+        Ok(filter)
+    }
+
+    fn and(&mut self) -> crate::Result<Filter> {
+        let mut filter = self.unary()?;
+        while self.keyword("and") {
+            filter = filter.and(self.unary()?);
+        }
+        Ok(filter)
+    }
+
+    fn unary(&mut self) -> crate::Result<Filter> {
+        if self.keyword("not") {
+            return Ok(!self.unary()?);
+        }
+        if self.peek() == Some(&Token::Open) {
+            self.position += 1;
+            let filter = self.or()?;
+            return match self.next() {
+                Some(Token::Close) => Ok(filter),
+                _ => {
+                    self.position -= 1;
+                    Err(self.error("Expected )"))
+                }
+            };
+        }
+        self.condition()
+    }
+
+    fn literal(&mut self, field_type: FieldType) -> crate::Result<Value> {
+        let literal = match self.peek() {
+            Some(Token::Word(literal)) | Some(Token::Quoted(literal)) => literal.clone(),
+            _ => return Err(self.error("Expected a value")),
+        };
+        let value = Value::parse(field_type, &literal).map_err(|_| {
+            self.error(&format!(
+                "{} is not a valid {:?} value",
+                literal, field_type
+            ))
+        })?;
+        self.position += 1;
+        Ok(value)
+    }
+
+    fn condition(&mut self) -> crate::Result<Filter> {
+        let field = match self.peek() {
+            Some(Token::Word(name)) => {
+                Field::from_str(name).map_err(|_| self.error(&format!("Unknown field {}", name)))?
+            }
+            _ => return Err(self.error("Expected a field")),
+        };
+        self.position += 1;
+        let field_type = field.field_type();
+        if self.keyword("in") {
+            if self.next() != Some(&Token::Open) {
+                self.position -= 1;
+                return Err(self.error("Expected ( after in"));
+            }
+            let mut values = vec![self.literal(field_type)?];
+            loop {
+                match self.next() {
+                    Some(Token::Comma) => values.push(self.literal(field_type)?),
+                    Some(Token::Close) => return Ok(Filter::one_of(field, values)),
+                    _ => {
+                        self.position -= 1;
+                        return Err(self.error("Expected , or )"));
+                    }
+                }
+            }
+        }
+        let op = match self.peek() {
+            Some(Token::Op(op)) => *op,
+            // A boolean field on its own, e.g. not deleted
+            _ if field_type == FieldType::Bool => return Ok(Filter::is(field)),
+            _ => return Err(self.error(&format!("Expected an operator after {}", field))),
+        };
+        let op = match (op, field_type) {
+            ("~", FieldType::Text) => None,
+            ("=", _) => Some(Op::Eq),
+            ("!=", _) => Some(Op::Ne),
+            ("<", FieldType::Number | FieldType::Time) => Some(Op::Lt),
+            ("<=", FieldType::Number | FieldType::Time) => Some(Op::Le),
+            (">", FieldType::Number | FieldType::Time) => Some(Op::Gt),
+            (">=", FieldType::Number | FieldType::Time) => Some(Op::Ge),
+            _ => return Err(self.error(&format!("{} can not be used with {}", op, field))),
+        };
+        self.position += 1;
+        match op {
+            Some(op) => Ok(Filter::compare(field, op, self.literal(field_type)?)),
+            None => {
+                let regex = match self.peek() {
+                    Some(Token::Word(regex)) | Some(Token::Quoted(regex)) => regex.clone(),
+                    _ => return Err(self.error("Expected a regex")),
+                };
+                let filter = Filter::matches(field, &regex)?;
+                self.position += 1;
+                Ok(filter)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, BASE_TIME, FLAG_IN_USE, SECOND};
+    use crate::{Parser, ParserSettings};
+
+    #[test]
+    fn parse_errors() {
+        for (expression, reason) in [
+            ("", "Expected a field at position 0"),
+            ("size <", "Expected a value at position 6"),
+            ("size < 10XB", "10XB is not a valid Number value"),
+            (
+                "modified > yesterday",
+                "yesterday is not a valid Time value",
+            ),
+            ("colour = red", "Unknown field colour"),
+            ("path > a", "> can not be used with path"),
+            ("size ~ 1", "~ can not be used with size"),
+            ("(deleted", "Expected ) at position 8"),
+            ("ext in (exe dll)", "Expected , or ) at position 12"),
+            (
+                "deleted directory",
+                "Expected and, or or the end of the filter",
+            ),
+            ("path = \"a", "Unterminated quote at position 7"),
+            ("path ~ \"(\"", "Filter regex ("),
+        ] {
+            let error = Filter::parse(expression).unwrap_err().to_string();
+            assert!(error.contains(reason), "{}: {}", expression, error);
+        }
+        assert_eq!(parse_number("10MB"), Some(10 << 20));
+        assert_eq!(parse_number("0x20"), Some(0x20));
+        assert_eq!(
+            parse_time("2024-01-01"),
+            parse_time("2024-01-01T02:00:00+02:00")
+        );
+    }
+
+    fn record(parser: &mut Parser, entry_id: u64) -> Record {
+        let block = parser.block_at(entry_id).unwrap();
+        Record::from(parser, &block).unwrap()
+    }
+
+    #[test]
+    fn eval_records() {
+        let times = fixtures::Times::all(BASE_TIME + SECOND);
+        let deleted = fixtures::record(
+            18,
+            1,
+            0,
+            &[
+                fixtures::standard_information(&times, 0x20),
+                fixtures::file_name(16, 1, "Run.PS1", 1, &times, 1),
+                fixtures::data(None, b"x"),
+            ],
+        );
+        let times = fixtures::Times::all(BASE_TIME + 10 * SECOND);
+        let tool = fixtures::record(
+            17,
+            1,
+            FLAG_IN_USE,
+            &[
+                fixtures::standard_information(&times, 0x20),
+                fixtures::file_name(16, 1, "tool.exe", 1, &times, 2048),
+                fixtures::non_resident_data(None, &[0x11, 0x01, 0x01, 0x00], 2048),
+            ],
+        );
+        let mft = fixtures::volume(&[
+            fixtures::directory(16, 5, "Tools", BASE_TIME),
+            tool,
+            deleted,
+        ]);
+        let path = fixtures::write_temp("filter", &mft);
+        let mut parser = Parser::new(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let records: Vec<Record> = (16..19).map(|id| record(&mut parser, id)).collect();
+        let matching = |expression: &str| -> Vec<u64> {
+            let filter = Filter::parse(expression).unwrap();
+            records
+                .iter()
+                .filter(|r| filter.eval(*r) == Some(true))
+                .map(|r| r.entry_id)
+                .collect()
+        };
+        assert_eq!(matching("ext in (exe, dll, ps1)"), [17, 18]);
+        assert_eq!(matching("ext in (exe,dll,ps1) and not deleted"), [17]);
+        assert_eq!(matching("size >= 2KB and size < 10MB"), [17]);
+        assert_eq!(matching("modified > 2023-01-01T00:00:05"), [17]);
+        assert_eq!(matching("directory or deleted = true"), [16, 18]);
+        assert_eq!(matching("not (directory or deleted)"), [17]);
+        assert_eq!(matching("path ~ '^\\{root\\}/tools/.*\\.exe$'"), [17]);
+        assert_eq!(matching("FILENAME = 'run.ps1' or entry_id = 16"), [16, 18]);
+        assert_eq!(
+            matching("parent_entry_id = 16 and file_attributes = 0x20"),
+            [17, 18]
+        );
+
+        // Filters on the path are unknown before it is resolved, unless the rest decides them
+        let header = parser.entry(17).unwrap().header;
+        let fields = EntryFields {
+            entry_id: 17,
+            header: &header,
+            standard_info: None,
+        };
+        let eval = |expression: &str| Filter::parse(expression).unwrap().eval(&fields);
+        assert_eq!(eval("ext = exe"), None);
+        assert_eq!(eval("ext = exe and deleted"), Some(false));
+        assert_eq!(eval("ext = exe or not deleted"), Some(true));
+        assert_eq!(eval("modified > 2023-01-01"), None);
+    }
+
+    #[test]
+    fn filter_setting() {
+        let mft = fixtures::volume(&[
+            fixtures::directory(16, 5, "Tools", BASE_TIME),
+            fixtures::file(17, 16, "tool.exe", b"MZ", BASE_TIME),
+            fixtures::file(18, 16, "readme.txt", b"", BASE_TIME),
+        ]);
+        let path = fixtures::write_temp("filter_setting", &mft);
+        let settings = ParserSettings::new()
+            .streaming(true)
+            .filter("not deleted and entry_id >= 17 and (ext = exe or size > 0)")
+            .unwrap();
+        let parser = Parser::with_settings(&path, settings).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let mut iter = crate::Iterator::from(parser);
+        let ids: Vec<u64> = iter.by_ref().map(|r| r.unwrap().entry_id).collect();
+        assert_eq!(ids, [17]);
+        // Only the paths of the entries that could still match were resolved
+        let cached = &iter.inner.path_parts;
+        assert!(cached.get(17).is_some() && cached.get(18).is_some());
+        assert!(cached.get(3).is_none());
+        assert!(ParserSettings::new().filter("size <").is_err());
+    }
+}
@@ -0,0 +1,243 @@
+// Synthetic MFT builder for tests, only depends on std so integration tests can include it by path
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+pub const RECORD_SIZE: usize = 1024;
+pub const USN: [u8; 2] = [0x07, 0x00];
+
+// 2023-01-01T00:00:00.1234567Z as a FILETIME
+pub const BASE_TIME: u64 = 133_170_048_001_234_567;
+pub const SECOND: u64 = 10_000_000;
+
+pub const FLAG_IN_USE: u16 = 0x01;
+pub const FLAG_DIRECTORY: u16 = 0x02;
+
+pub struct Times {
+    pub created: u64,
+    pub modified: u64,
+    pub mft_modified: u64,
+    pub accessed: u64,
+}
+
+impl Times {
+    pub fn all(time: u64) -> Self {
+        Self {
+            created: time,
+            modified: time,
+            mft_modified: time,
+            accessed: time,
+        }
+    }
+}
+
+fn resident_attribute(type_code: u32, name: Option<&str>, data: &[u8]) -> Vec<u8> {
+    let name: Vec<u16> = name.map(|n| n.encode_utf16().collect()).unwrap_or_default();
+    let name_offset = 24;
+    let data_offset = (name_offset + name.len() * 2 + 7) & !7;
+    let record_len = (data_offset + data.len() + 7) & !7;
+    let mut buffer = vec![0; record_len];
+    buffer[0..4].copy_from_slice(&type_code.to_le_bytes());
+    buffer[4..8].copy_from_slice(&(record_len as u32).to_le_bytes());
+    buffer[8] = 0; // Resident
+    buffer[9] = name.len() as u8;
+    buffer[10..12].copy_from_slice(&(name_offset as u16).to_le_bytes());
+    // Flags (12..14) and instance (14..16) are filled in by record()
+    buffer[16..20].copy_from_slice(&(data.len() as u32).to_le_bytes());
+    buffer[20..22].copy_from_slice(&(data_offset as u16).to_le_bytes());
+    for (i, c) in name.iter().enumerate() {
+        buffer[name_offset + i * 2..name_offset + i * 2 + 2].copy_from_slice(&c.to_le_bytes());
+    }
+    buffer[data_offset..data_offset + data.len()].copy_from_slice(data);
+    buffer
+}
+
+pub fn standard_information(times: &Times, file_attributes: u32) -> Vec<u8> {
+    let mut data = Vec::new();
+    for time in [
+        times.created,
+        times.modified,
+        times.mft_modified,
+        times.accessed,
+    ] {
+        data.extend_from_slice(&time.to_le_bytes());
+    }
+    data.extend_from_slice(&file_attributes.to_le_bytes());
+    data.extend_from_slice(&[0; 12]); // Max versions, version number, class id
+    data.extend_from_slice(&0u32.to_le_bytes()); // Owner id
+    data.extend_from_slice(&0x100u32.to_le_bytes()); // Security id
+    data.extend_from_slice(&0u64.to_le_bytes()); // Quota charged
+    data.extend_from_slice(&0u64.to_le_bytes()); // USN
+    resident_attribute(0x10, None, &data)
+}
+
+pub fn file_name(
+    parent_entry: u64,
+    parent_sequence: u16,
+    name: &str,
+    name_space: u8,
+    times: &Times,
+    size: u64,
+) -> Vec<u8> {
+    let name: Vec<u16> = name.encode_utf16().collect();
+    let parent = parent_entry | (parent_sequence as u64) << 48;
+    let mut data = Vec::new();
+    data.extend_from_slice(&parent.to_le_bytes());
+    for time in [
+        times.created,
+        times.modified,
+        times.mft_modified,
+        times.accessed,
+    ] {
+        data.extend_from_slice(&time.to_le_bytes());
+    }
+    data.extend_from_slice(&((size + 4095) & !4095).to_le_bytes()); // Allocated size
+    data.extend_from_slice(&size.to_le_bytes());
+    data.extend_from_slice(&0u32.to_le_bytes()); // Flags
+    data.extend_from_slice(&0u32.to_le_bytes()); // Reparse value
+    data.push(name.len() as u8);
+    data.push(name_space);
+    for c in name {
+        data.extend_from_slice(&c.to_le_bytes());
+    }
+    resident_attribute(0x30, None, &data)
+}
+
+pub fn data(name: Option<&str>, content: &[u8]) -> Vec<u8> {
+    resident_attribute(0x80, name, content)
+}
+
+// Non-resident $DATA, runs is the encoded run list and clusters are 4096 bytes
+pub fn non_resident_data(name: Option<&str>, runs: &[u8], size: u64) -> Vec<u8> {
+    let name: Vec<u16> = name.map(|n| n.encode_utf16().collect()).unwrap_or_default();
+    let name_offset = 64;
+    let runs_offset = (name_offset + name.len() * 2 + 7) & !7;
+    let record_len = (runs_offset + runs.len() + 1 + 7) & !7;
+    let allocated = size.div_ceil(4096) * 4096;
+    let mut buffer = vec![0; record_len];
+    buffer[0..4].copy_from_slice(&0x80u32.to_le_bytes());
+    buffer[4..8].copy_from_slice(&(record_len as u32).to_le_bytes());
+    buffer[8] = 1; // Non-resident
+    buffer[9] = name.len() as u8;
+    buffer[10..12].copy_from_slice(&(name_offset as u16).to_le_bytes());
+    buffer[24..32].copy_from_slice(&(allocated / 4096).saturating_sub(1).to_le_bytes()); // Highest VCN
+    buffer[32..34].copy_from_slice(&(runs_offset as u16).to_le_bytes());
+    buffer[40..48].copy_from_slice(&allocated.to_le_bytes());
+    buffer[48..56].copy_from_slice(&size.to_le_bytes());
+    buffer[56..64].copy_from_slice(&size.to_le_bytes()); // Initialized size
+    for (i, c) in name.iter().enumerate() {
+        buffer[name_offset + i * 2..name_offset + i * 2 + 2].copy_from_slice(&c.to_le_bytes());
+    }
+    buffer[runs_offset..runs_offset + runs.len()].copy_from_slice(runs);
+    buffer
+}
+
+// Builds a FILE record with the given attributes, instance ids are assigned in order
+pub fn record(entry: u32, sequence: u16, flags: u16, attributes: &[Vec<u8>]) -> Vec<u8> {
+    sized_record(RECORD_SIZE, entry, sequence, flags, attributes)
+}
+
+// record() with another record size, attributes start after the larger fixup array
+pub fn sized_record(
+    size: usize,
+    entry: u32,
+    sequence: u16,
+    flags: u16,
+    attributes: &[Vec<u8>],
+) -> Vec<u8> {
+    let fixups = size / 512 + 1;
+    let attributes_offset = (0x30 + fixups * 2).div_ceil(8) * 8;
+    let mut buffer = vec![0; size];
+    buffer[0..4].copy_from_slice(b"FILE");
+    buffer[4..6].copy_from_slice(&0x30u16.to_le_bytes()); // Offset to fixup
+    buffer[6..8].copy_from_slice(&(fixups as u16).to_le_bytes()); // Fixup count
+    buffer[8..16].copy_from_slice(&(0x1000 + entry as u64).to_le_bytes()); // LSN
+    buffer[16..18].copy_from_slice(&sequence.to_le_bytes());
+    buffer[18..20].copy_from_slice(&1u16.to_le_bytes()); // Link count
+    buffer[20..22].copy_from_slice(&(attributes_offset as u16).to_le_bytes()); // Attributes offset
+    buffer[22..24].copy_from_slice(&flags.to_le_bytes());
+    buffer[28..32].copy_from_slice(&(size as u32).to_le_bytes());
+    buffer[44..48].copy_from_slice(&entry.to_le_bytes());
+    //
+    let mut offset = attributes_offset;
+    for (instance, attribute) in attributes.iter().enumerate() {
+        let mut attribute = attribute.clone();
+        attribute[14..16].copy_from_slice(&(instance as u16).to_le_bytes());
+        buffer[offset..offset + attribute.len()].copy_from_slice(&attribute);
+        offset += attribute.len();
+    }
+    buffer[offset..offset + 4].copy_from_slice(&0xFFFF_FFFFu32.to_le_bytes());
+    offset += 8;
+    buffer[24..28].copy_from_slice(&(offset as u32).to_le_bytes()); // Used size
+    buffer[40..42].copy_from_slice(&(attributes.len() as u16).to_le_bytes()); // Next attribute id
+    protect(&mut buffer);
+    buffer
+}
+
+// Moves the sector tails into the fixup array and replaces them with the update sequence number
+pub fn protect(buffer: &mut [u8]) {
+    buffer[0x30..0x32].copy_from_slice(&USN);
+    for sector in 1..=(buffer.len() / 512) {
+        let tail = sector * 512 - 2;
+        let slot = 0x30 + sector * 2;
+        let original = [buffer[tail], buffer[tail + 1]];
+        buffer[slot..slot + 2].copy_from_slice(&original);
+        buffer[tail..tail + 2].copy_from_slice(&USN);
+    }
+}
+
+// A simple file record: $STANDARD_INFORMATION, a Win32 $FILE_NAME and a resident $DATA
+pub fn file(entry: u32, parent_entry: u64, name: &str, content: &[u8], time: u64) -> Vec<u8> {
+    let times = Times::all(time);
+    record(
+        entry,
+        1,
+        FLAG_IN_USE,
+        &[
+            standard_information(&times, 0x20),
+            file_name(parent_entry, 1, name, 1, &times, content.len() as u64),
+            data(None, content),
+        ],
+    )
+}
+
+pub fn directory(entry: u32, parent_entry: u64, name: &str, time: u64) -> Vec<u8> {
+    let times = Times::all(time);
+    record(
+        entry,
+        1,
+        FLAG_IN_USE | FLAG_DIRECTORY,
+        &[
+            standard_information(&times, 0x10),
+            file_name(parent_entry, 1, name, 3, &times, 0),
+        ],
+    )
+}
+
+// Entries 0-15 mimic the system files (with 5 as the root), everything else follows
+pub fn volume(extra: &[Vec<u8>]) -> Vec<u8> {
+    let system_files = [
+        "$MFT", "$MFTMirr", "$LogFile", "$Volume", "$AttrDef", ".", "$Bitmap", "$Boot", "$BadClus",
+        "$Secure", "$UpCase", "$Extend",
+    ];
+    let mut mft = Vec::new();
+    for (entry, name) in system_files.iter().enumerate() {
+        if *name == "." || *name == "$Extend" {
+            mft.extend(directory(entry as u32, 5, name, BASE_TIME));
+        } else {
+            mft.extend(file(entry as u32, 5, name, &[], BASE_TIME));
+        }
+    }
+    // Reserved, unused entries
+    mft.extend(vec![0; RECORD_SIZE * (16 - system_files.len())]);
+    for record in extra {
+        mft.extend(record);
+    }
+    mft
+}
+
+pub fn write_temp(name: &str, bytes: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("mft-{}-{}.mft", std::process::id(), name));
+    std::fs::write(&path, bytes).expect("Unable to write test fixture");
+    path
+}
@@ -0,0 +1,210 @@
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::raw::Entry;
+
+// MFT input, either read through a buffered file, straight from a memory mapping or from memory
+#[derive(Debug)]
+pub enum Input {
+    // Position is kept alongside the reader so asking for it does not cost a seek on the file
+    File {
+        reader: BufReader<File>,
+        position: u64,
+    },
+    #[cfg(feature = "mmap")]
+    Mapped(Cursor<MappedFile>),
+    // Inputs that cannot be reopened or mapped, e.g. stdin
+    Memory(Cursor<SharedBuffer>),
+}
+
+// Buffer shared between worker parsers, cloning only clones the Arc
+#[derive(Debug, Clone)]
+pub struct SharedBuffer(Arc<Vec<u8>>);
+
+impl AsRef<[u8]> for SharedBuffer {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+// Mapping shared between worker parsers, cloning only clones the Arc
+#[cfg(feature = "mmap")]
+#[derive(Debug, Clone)]
+pub struct MappedFile(Arc<memmap2::Mmap>);
+
+#[cfg(feature = "mmap")]
+impl AsRef<[u8]> for MappedFile {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Input {
+    pub fn open<P: AsRef<Path>>(path: P, mmap: bool) -> crate::Result<Self> {
+        let file = File::open(path)?;
+        match mmap {
+            false => Ok(Input::File {
+                reader: BufReader::new(file),
+                position: 0,
+            }),
+            #[cfg(feature = "mmap")]
+            true => {
+                // Safety: the mapping is read only, the file being truncated by another process
+                // while parsing is not something we can guard against (same as any mmap user)
+                let map = unsafe { memmap2::Mmap::map(&file)? };
+                Ok(Input::Mapped(Cursor::new(MappedFile(Arc::new(map)))))
+            }
+            #[cfg(not(feature = "mmap"))]
+            true => Err(crate::Error::InvalidSetting(String::from(
+                "mmap input requires the mmap feature",
+            ))),
+        }
+    }
+
+    // Reads the whole of reader into memory
+    pub fn from_reader<R: Read>(mut reader: R) -> crate::Result<Self> {
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer)?;
+        Ok(Input::Memory(Cursor::new(SharedBuffer(Arc::new(buffer)))))
+    }
+
+    pub fn len(&self) -> crate::Result<u64> {
+        match self {
+            Input::File { reader, .. } => Ok(reader.get_ref().metadata()?.len()),
+            #[cfg(feature = "mmap")]
+            Input::Mapped(cursor) => Ok(cursor.get_ref().as_ref().len() as u64),
+            Input::Memory(cursor) => Ok(cursor.get_ref().as_ref().len() as u64),
+        }
+    }
+
+    pub fn is_empty(&self) -> crate::Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    // A second independent reader over the same input, used by worker parsers
+    pub fn try_clone(&self, path: &Path) -> crate::Result<Self> {
+        match self {
+            Input::File { .. } => Self::open(path, false),
+            #[cfg(feature = "mmap")]
+            Input::Mapped(cursor) => Ok(Input::Mapped(Cursor::new(cursor.get_ref().clone()))),
+            Input::Memory(cursor) => Ok(Input::Memory(Cursor::new(cursor.get_ref().clone()))),
+        }
+    }
+
+    // Whole input when it is mapped or in memory, None when reading through a file
+    pub fn as_slice(&self) -> Option<&[u8]> {
+        match self {
+            Input::File { .. } => None,
+            #[cfg(feature = "mmap")]
+            Input::Mapped(cursor) => Some(cursor.get_ref().as_ref()),
+            Input::Memory(cursor) => Some(cursor.get_ref().as_ref()),
+        }
+    }
+
+    // Entry at offset with fixups applied, copied straight from the input when it is mapped or
+    // in memory so the reader is not moved
+    pub fn read_entry_bytes(&mut self, offset: u64, buffer: &mut Vec<u8>) -> crate::Result<()> {
+        match self.as_slice() {
+            Some(input) => Entry::copy_entry_bytes(input, offset, buffer),
+            None => Entry::read_entry_bytes(self, offset, buffer),
+        }
+    }
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Input::File { reader, position } => {
+                let read = reader.read(buf)?;
+                *position += read as u64;
+                Ok(read)
+            }
+            #[cfg(feature = "mmap")]
+            Input::Mapped(cursor) => cursor.read(buf),
+            Input::Memory(cursor) => cursor.read(buf),
+        }
+    }
+}
+
+impl Seek for Input {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            Input::File { reader, position } => {
+                *position = reader.seek(pos)?;
+                Ok(*position)
+            }
+            #[cfg(feature = "mmap")]
+            Input::Mapped(cursor) => cursor.seek(pos),
+            Input::Memory(cursor) => cursor.seek(pos),
+        }
+    }
+
+    // Used by read_value! before every field, must not reach the file
+    fn stream_position(&mut self) -> std::io::Result<u64> {
+        match self {
+            Input::File { position, .. } => Ok(*position),
+            #[cfg(feature = "mmap")]
+            Input::Mapped(cursor) => Ok(cursor.position()),
+            Input::Memory(cursor) => Ok(cursor.position()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Input;
+    use crate::fixtures;
+    use std::io::{Read, Seek, SeekFrom};
+
+    #[test]
+    fn file_position_is_kept() {
+        let path = fixtures::write_temp("file_position", &fixtures::volume(&[]));
+        let mut input = Input::open(&path, false).unwrap();
+        let mut buffer = [0; 100];
+        input.read_exact(&mut buffer).unwrap();
+        assert_eq!(input.stream_position().unwrap(), 100);
+        input.seek(SeekFrom::Current(-10)).unwrap();
+        input.read_exact(&mut buffer[..5]).unwrap();
+        assert_eq!(input.stream_position().unwrap(), 95);
+        input.seek(SeekFrom::Start(4096)).unwrap();
+        let mut rest = Vec::new();
+        input.read_to_end(&mut rest).unwrap();
+        assert_eq!(input.stream_position().unwrap(), input.len().unwrap());
+        match &mut input {
+            Input::File { reader, position } => {
+                assert_eq!(reader.stream_position().unwrap(), *position)
+            }
+            _ => unreachable!(),
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mmap_matches_file() {
+        use crate::fixtures::BASE_TIME;
+        use crate::{Formatted, Parser, ParserSettings};
+        let mut extra = vec![fixtures::directory(16, 5, "dir", BASE_TIME)];
+        for entry in 17..64 {
+            let name = format!("file{}.txt", entry);
+            extra.push(fixtures::file(entry, 16, &name, b"data", BASE_TIME));
+        }
+        let path = fixtures::write_temp("mmap", &fixtures::volume(&extra));
+        let rows = |mmap: bool, threads: usize| -> Vec<String> {
+            let settings = ParserSettings::new()
+                .drive_char('C')
+                .mmap(mmap)
+                .threads(threads);
+            let parser = Parser::with_settings(&path, settings).unwrap();
+            assert_eq!(parser.reader.as_slice().is_some(), mmap);
+            Formatted::from(parser).collect()
+        };
+        let file = rows(false, 1);
+        assert_eq!(file.len(), 1 + 12 + 48);
+        assert_eq!(file, rows(true, 1));
+        assert_eq!(file, rows(true, 3));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,23 +1,110 @@
 use chrono::{DateTime, Utc};
-use std::io::{Seek, SeekFrom};
+use std::collections::VecDeque;
 use std::path::PathBuf;
 
-use crate::attributes::StandardInformation;
+use crate::attributes::{Data, FileName, StandardInformation};
 use crate::block::{Block, BlockType};
+use crate::csv::Column;
+use crate::filter::EntryFields;
+use crate::raw::{RecordStatus, SlackAttribute};
+use crate::sink::{OutputType, RecordSink};
+use crate::timestomp::TimestompReason;
 use crate::Parser;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct Record {
     pub entry_id: u64,
+    pub sequence_number: u16,
+    // Entry ID in the low 48 bits and sequence number in the high 16, as NTFS references it
+    pub file_reference: u64,
     pub path: PathBuf,
     pub is_file: bool,
     pub is_deleted: bool,
     pub filename: Option<String>,
-    //
+    pub name_space: Option<u8>,
+    pub parent_entry_id: Option<u64>,
+    pub parent_sequence_number: Option<u16>,
+    // Logical and allocated size of the unnamed $DATA stream, or from $FILE_NAME without one
+    pub size: u64,
+    pub allocated_size: u64,
+    // Record header
+    pub flags: u16,
+    pub link_count: u16,
+    pub lsn: u64,
+    // $STANDARD_INFORMATION
+    pub file_attributes: u32,
+    pub owner_id: u32,
+    pub security_id: u32,
+    pub usn: u64,
+    // $STANDARD_INFORMATION times
     pub created: DateTime<Utc>,
     pub modified: DateTime<Utc>,
     pub accessed: DateTime<Utc>,
+    pub changed: DateTime<Utc>, // MFT entry modified
+    pub created_raw: u64,
+    pub modified_raw: u64,
+    pub accessed_raw: u64,
+    pub changed_raw: u64,
+    // $FILE_NAME times of the filename used for the path
+    pub fn_created: Option<DateTime<Utc>>,
+    pub fn_modified: Option<DateTime<Utc>>,
+    pub fn_accessed: Option<DateTime<Utc>>,
+    pub fn_changed: Option<DateTime<Utc>>,
+    pub fn_created_raw: Option<u64>,
+    pub fn_modified_raw: Option<u64>,
+    pub fn_accessed_raw: Option<u64>,
+    pub fn_changed_raw: Option<u64>,
+    // Every $FILE_NAME held in the base record, including DOS names and hard links
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub file_names: Vec<FileNameTimes>,
+    // Contents of a resident Zone.Identifier stream
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zone_identifier: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub timestomp: Vec<TimestompReason>,
     //
+    pub status: RecordStatus,
+    // Set on diagnostic rows for entries that could not be turned into a full record
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    // Only populated when ParserSettings::scan_slack is set
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub slack: Vec<SlackAttribute>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileNameTimes {
+    pub name: String,
+    pub name_space: u8,
+    pub parent_entry_id: u64,
+    pub parent_sequence_number: u16,
+    pub created: DateTime<Utc>,
+    pub modified: DateTime<Utc>,
+    pub accessed: DateTime<Utc>,
+    pub changed: DateTime<Utc>,
+    pub created_raw: u64,
+    pub modified_raw: u64,
+    pub accessed_raw: u64,
+    pub changed_raw: u64,
+}
+
+impl From<FileName> for FileNameTimes {
+    fn from(file_name: FileName) -> Self {
+        Self {
+            name: file_name.name,
+            name_space: file_name.name_space,
+            parent_entry_id: file_name.parent_file_reference.entry,
+            parent_sequence_number: file_name.parent_file_reference.sequence,
+            created: file_name.creation_time,
+            modified: file_name.modification_time,
+            accessed: file_name.access_time,
+            changed: file_name.mft_modification_time,
+            created_raw: file_name.creation_time_raw,
+            modified_raw: file_name.modification_time_raw,
+            accessed_raw: file_name.access_time_raw,
+            changed_raw: file_name.mft_modification_time_raw,
+        }
+    }
 }
 
 impl Record {
@@ -26,129 +113,509 @@ impl Record {
         let path = parser.get_file_path(block.entry_id)?;
         let filename = path.file_name().map(|f| f.to_string_lossy().to_string());
         //
-        let standard_info_block = block
-            .blocks
-            .iter()
-            .find(|b| BlockType::StandardInformation == b.block_type)
-            .ok_or_else(|| crate::Error::missing_block("StandardInfo", block.entry_id))?;
+        // Every attribute is decoded from one copy of the entry with its fixups applied
+        let entry_block = block
+            .pointer(BlockType::Entry)
+            .ok_or_else(|| crate::Error::missing_block("EntryBlock", block.entry_id))?;
+        let entry_offset = entry_block.offset;
         parser
             .reader
-            .seek(SeekFrom::Start(standard_info_block.offset))?;
-        let standard_info = StandardInformation::from_reader(&mut parser.reader)?;
+            .read_entry_bytes(entry_offset, &mut parser.entry_bytes)
+            .map_err(|e| e.for_entry(block.entry_id))?;
+        let entry_bytes = parser.entry_bytes.as_slice();
+        let in_entry = |e: crate::Error| e.in_entry(block.entry_id, entry_offset);
+        //
+        let standard_info_block = block
+            .pointer(BlockType::StandardInformation)
+            .ok_or_else(|| crate::Error::missing_block("StandardInfo", block.entry_id))?;
+        let standard_info = StandardInformation::from_reader(
+            &mut standard_info_block.reader(entry_bytes, entry_offset),
+        )
+        .map_err(|e| in_entry(e.in_attribute(0x10)))?;
         let is_file = standard_info.file_attributes != 0x00000010;
         let created = standard_info.creation_time;
         let modified = standard_info.modification_time;
         let accessed = standard_info.access_time;
+        let changed = standard_info.mft_modification_time;
         //
-        let entry_block = block
-            .blocks
-            .iter()
-            .find(|b| BlockType::Entry == b.block_type)
-            .ok_or_else(|| crate::Error::missing_block("EntryBlock", block.entry_id))?;
-        parser.reader.seek(SeekFrom::Start(entry_block.offset))?;
-        let entry_header = crate::raw::Header::from_reader(&mut parser.reader)?;
-        let is_deleted = entry_header.flags.to_le_bytes().contains(&0x02);
+        let entry_header =
+            crate::raw::Header::from_reader(&mut entry_block.reader(entry_bytes, entry_offset))
+                .map_err(in_entry)?;
+        let is_deleted = entry_header.flags & 0x01 == 0;
+        //
+        let mut file_names = Vec::new();
+        for file_name_block in block.pointers(BlockType::FileName) {
+            let file_name =
+                FileName::from_reader(&mut file_name_block.reader(entry_bytes, entry_offset))
+                    .map_err(|e| in_entry(e.in_attribute(0x30)))?;
+            file_names.push(file_name);
+        }
+        // The size was checked against the attribute when the block was built
+        let zone_identifier = block
+            .pointers(BlockType::ZoneIdentifier)
+            .find(|b| b.is_resident)
+            .and_then(|zone_block| {
+                let start = zone_block.offset.checked_sub(entry_offset)? as usize;
+                entry_bytes.get(start..start.checked_add(zone_block.size as usize)?)
+            })
+            .and_then(|buffer| match Data::from_buffer(buffer, true) {
+                Ok(Data::ZoneIdentifier(zone)) => Some(zone),
+                _ => None,
+            });
+        //
+        let slack = match parser.settings.scan_slack {
+            true => crate::raw::Entry::scan_slack_bytes(entry_bytes, &entry_header, entry_offset),
+            false => Vec::new(),
+        };
+        // The name get_best_path_part picks, it only has to be looked up again for names that
+        // are behind an attribute list
+        let file_name = match block.pointer(BlockType::AttributeList) {
+            Some(_) => parser.get_best_path_part(block.entry_id).ok(),
+            None => file_names.iter().find(|f| f.name_space != 2).cloned(),
+        };
+        let timestomp = parser.timestomp.check(&standard_info, file_name.as_ref());
+        // Unnamed $DATA sorts before any named streams
+        let (size, allocated_size) = block
+            .pointer(BlockType::Data)
+            .map(|b| (b.size, b.allocated_size))
+            .or_else(|| file_name.as_ref().map(|f| (f.real_size, f.allocated_size)))
+            .unwrap_or_default();
         //
         Ok(Self {
             entry_id: block.entry_id,
+            sequence_number: entry_header.sequence_number,
+            file_reference: block.entry_id | (entry_header.sequence_number as u64) << 48,
             path,
             is_file,
             is_deleted,
             filename,
+            name_space: file_name.as_ref().map(|f| f.name_space),
+            parent_entry_id: file_name.as_ref().map(|f| f.parent_file_reference.entry),
+            parent_sequence_number: file_name.as_ref().map(|f| f.parent_file_reference.sequence),
+            size,
+            allocated_size,
+            flags: entry_header.flags,
+            link_count: entry_header.link_count,
+            lsn: entry_header.log_sequence_number,
+            file_attributes: standard_info.file_attributes,
+            owner_id: standard_info.owner_id,
+            security_id: standard_info.security_id,
+            usn: standard_info.update_sequence_number,
             created,
             modified,
             accessed,
+            changed,
+            created_raw: standard_info.creation_time_raw,
+            modified_raw: standard_info.modification_time_raw,
+            accessed_raw: standard_info.access_time_raw,
+            changed_raw: standard_info.mft_modification_time_raw,
+            fn_created: file_name.as_ref().map(|f| f.creation_time),
+            fn_modified: file_name.as_ref().map(|f| f.modification_time),
+            fn_accessed: file_name.as_ref().map(|f| f.access_time),
+            fn_changed: file_name.as_ref().map(|f| f.mft_modification_time),
+            fn_created_raw: file_name.as_ref().map(|f| f.creation_time_raw),
+            fn_modified_raw: file_name.as_ref().map(|f| f.modification_time_raw),
+            fn_accessed_raw: file_name.as_ref().map(|f| f.access_time_raw),
+            fn_changed_raw: file_name.as_ref().map(|f| f.mft_modification_time_raw),
+            file_names: file_names.into_iter().map(FileNameTimes::from).collect(),
+            zone_identifier,
+            timestomp,
+            status: block.status,
+            error: None,
+            slack,
         })
     }
+
+    // Checks the filter against the header and then $STANDARD_INFORMATION so records that can not
+    // match are skipped before their path is resolved. Read errors are left for Record::from
+    fn prefilter(parser: &mut Parser, block: &Block) -> bool {
+        let filter = match &parser.settings.filter {
+            Some(filter) => filter,
+            None => return true,
+        };
+        let entry_block = match block.pointer(BlockType::Entry) {
+            Some(entry_block) => entry_block,
+            None => return true,
+        };
+        if parser
+            .reader
+            .read_entry_bytes(entry_block.offset, &mut parser.entry_bytes)
+            .is_err()
+        {
+            return true;
+        }
+        let entry_bytes = parser.entry_bytes.as_slice();
+        let header = match crate::raw::Header::from_reader(
+            &mut entry_block.reader(entry_bytes, entry_block.offset),
+        ) {
+            Ok(header) => header,
+            Err(_) => return true,
+        };
+        let mut fields = EntryFields {
+            entry_id: block.entry_id,
+            header: &header,
+            standard_info: None,
+        };
+        if let Some(matched) = filter.eval(&fields) {
+            return matched;
+        }
+        let standard_info = match block.pointer(BlockType::StandardInformation).map(|b| {
+            StandardInformation::from_reader(&mut b.reader(entry_bytes, entry_block.offset))
+        }) {
+            Some(Ok(standard_info)) => standard_info,
+            _ => return true,
+        };
+        fields.standard_info = Some(&standard_info);
+        filter.eval(&fields) != Some(false)
+    }
+
+    // Row for an entry that is not a valid record or failed to parse, times are left as FILETIME 0
+    pub fn diagnostic(block: &Block, error: Option<String>) -> Self {
+        let zero_time = crate::attributes::convert_u64_to_datetime(0);
+        Self {
+            entry_id: block.entry_id,
+            sequence_number: 0,
+            file_reference: block.entry_id,
+            path: PathBuf::new(),
+            is_file: false,
+            is_deleted: false,
+            filename: None,
+            name_space: None,
+            parent_entry_id: None,
+            parent_sequence_number: None,
+            size: 0,
+            allocated_size: 0,
+            flags: 0,
+            link_count: 0,
+            lsn: 0,
+            file_attributes: 0,
+            owner_id: 0,
+            security_id: 0,
+            usn: 0,
+            created: zero_time,
+            modified: zero_time,
+            accessed: zero_time,
+            changed: zero_time,
+            created_raw: 0,
+            modified_raw: 0,
+            accessed_raw: 0,
+            changed_raw: 0,
+            fn_created: None,
+            fn_modified: None,
+            fn_accessed: None,
+            fn_changed: None,
+            fn_created_raw: None,
+            fn_modified_raw: None,
+            fn_accessed_raw: None,
+            fn_changed_raw: None,
+            file_names: Vec::new(),
+            zone_identifier: None,
+            timestomp: Vec::new(),
+            status: block.status,
+            error: error.or_else(|| block.error.as_ref().map(|e| e.message.clone())),
+            slack: Vec::new(),
+        }
+    }
 }
 
+// Blocks decoded by each worker thread per parallel batch
+const BATCH_SIZE: usize = 1024;
+
+// Yields a typed Record per entry, entries that could not be parsed are yielded as errors
 pub struct Iterator {
     pub inner: crate::Parser,
     pub next_entry_id: u64,
-    output_type: OutputType,
+    // Include diagnostic records for BAAD, garbage and unparseable blocks
+    pub include_diagnostics: bool,
+    // Stop write_to at the first entry that could not be parsed instead of writing a diagnostic row
+    pub fail_fast: bool,
+    // Decoded records waiting to be yielded, with the index of their block
+    buffer: VecDeque<(usize, crate::Result<Record>)>,
+    // Created on the first parallel batch when ParserSettings::threads is more than 1
+    workers: Vec<Parser>,
 }
 
-enum OutputType {
-    Csv,
-    Json,
-}
+impl Iterator {
+    pub fn new(parser: Parser) -> Self {
+        Self {
+            inner: parser,
+            next_entry_id: 0,
+            include_diagnostics: true,
+            fail_fast: false,
+            buffer: VecDeque::new(),
+            workers: Vec::new(),
+        }
+    }
+
+    pub fn include_diagnostics(mut self, include_diagnostics: bool) -> Self {
+        self.include_diagnostics = include_diagnostics;
+        self
+    }
+
+    pub fn fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    // Formats records with the output type and columns from the ParserSettings
+    pub fn formatted(self) -> Formatted {
+        let output_type = self.inner.settings.output_type;
+        Formatted::new(self, output_type)
+    }
 
-impl OutputType {
-    pub fn as_type(&self, record: Record) -> String {
-        match self {
-            OutputType::Csv => {
-                // Headers = "entry_id,path,is_file,is_deleted,filename,created,modified,accessed"
-                let csv = format!(
-                    "{},\"{}\",{},{},\"{}\",\"{}\",\"{}\",\"{}\"",
-                    record.entry_id,
-                    record.path.to_str().unwrap(),
-                    record.is_file,
-                    record.is_deleted,
-                    record.filename.unwrap_or_default(),
-                    record.created.to_rfc3339(),
-                    record.modified.to_rfc3339(),
-                    record.accessed.to_rfc3339(),
-                );
-                csv
+    // Record for a single block, None if the block is skipped
+    fn decode(
+        parser: &mut Parser,
+        index: usize,
+        with_diagnostics: bool,
+    ) -> Option<crate::Result<Record>> {
+        let block = match parser.block_at(index as u64) {
+            Ok(block) => block,
+            Err(e) => {
+                parser.record_error(e.sample(index as u64));
+                return Some(Err(e));
             }
-            OutputType::Json => {
-                // todo!("Handle this unwrap gracefully");
-                serde_json::to_string(&record).expect("An error occured whilst serializing record")
+        };
+        parser.record_streamed(&block);
+        if let Some(sample) = &block.error {
+            parser.record_error(sample.clone());
+        }
+        let record = match (block.status, &block.error) {
+            (RecordStatus::Zeroed, None) => return None,
+            (RecordStatus::Valid, None) => {
+                if !Record::prefilter(parser, &block) {
+                    return None;
+                }
+                Record::from(parser, &block).map_err(|e| e.for_entry(block.entry_id))
+            }
+            _ if !with_diagnostics => return None,
+            _ => Ok(Record::diagnostic(&block, None)),
+        };
+        match record {
+            Err(e) => {
+                parser.record_error(e.sample(block.entry_id));
+                Some(Err(e))
+            }
+            Ok(r) => {
+                let settings = &parser.settings;
+                let to_skip = settings
+                    .filter
+                    .as_ref()
+                    .is_some_and(|f| f.eval(&r) != Some(true))
+                    || !settings.is_included(r.path.to_str(), r.filename.as_deref());
+                match to_skip {
+                    true => None,
+                    false => Some(Ok(r)),
+                }
             }
         }
     }
+
+    fn fill_buffer(&mut self, with_diagnostics: bool) {
+        let threads = self.inner.settings.threads;
+        if threads > 1 && self.workers.is_empty() {
+            match (0..threads).map(|_| self.inner.worker()).collect() {
+                Ok(workers) => self.workers = workers,
+                Err(e) => {
+                    warn!(
+                        "Unable to create worker parsers, decoding on one thread: {}",
+                        e
+                    );
+                    self.inner.settings.threads = 1;
+                }
+            }
+        }
+        let block_count = self.inner.block_count() as usize;
+        // Loop to get blocks, allows for exclusion skips without next() recursion which causes stack overflows
+        while self.buffer.is_empty() && (self.next_entry_id as usize) < block_count {
+            let start = self.next_entry_id as usize;
+            if self.workers.is_empty() {
+                self.next_entry_id += 1;
+                if let Some(record) = Self::decode(&mut self.inner, start, with_diagnostics) {
+                    self.buffer.push_back((start, record));
+                }
+                continue;
+            }
+            let end = (start + self.workers.len() * BATCH_SIZE).min(block_count);
+            let chunk_size = (end - start).div_ceil(self.workers.len());
+            self.next_entry_id = end as u64;
+            // Each worker decodes a contiguous chunk, joining in order keeps the output in entry order
+            let chunks = std::thread::scope(|scope| {
+                let handles: Vec<_> = self
+                    .workers
+                    .iter_mut()
+                    .enumerate()
+                    .map(|(i, worker)| {
+                        let chunk_start = (start + i * chunk_size).min(end);
+                        let chunk_end = (chunk_start + chunk_size).min(end);
+                        let handle = scope.spawn(move || {
+                            (chunk_start..chunk_end)
+                                .filter_map(|index| {
+                                    Self::decode(worker, index, with_diagnostics)
+                                        .map(|record| (index, record))
+                                })
+                                .collect::<Vec<_>>()
+                        });
+                        (chunk_start, chunk_end, handle)
+                    })
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|(chunk_start, chunk_end, handle)| {
+                        handle.join().unwrap_or_else(|_| {
+                            let error = crate::Error::Internal(format!(
+                                "Worker thread panicked decoding entries {} to {}",
+                                chunk_start, chunk_end
+                            ));
+                            vec![(chunk_start, Err(error))]
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            });
+            self.buffer.extend(chunks.into_iter().flatten());
+        }
+    }
+
+    fn next_record(&mut self, with_diagnostics: bool) -> Option<(usize, crate::Result<Record>)> {
+        if self.buffer.is_empty() {
+            self.fill_buffer(with_diagnostics);
+        }
+        let (index, mut record) = self.buffer.pop_front()?;
+        // Done here rather than in Record::from as it needs every record in entry order
+        if let Ok(record) = &mut record {
+            if record.status == RecordStatus::Valid
+                && record.error.is_none()
+                && self.inner.timestomp.check_entry_order(record)
+            {
+                record.timestomp.push(TimestompReason::EntryOrder);
+            }
+        }
+        Some((index, record))
+    }
+
+    // Diagnostic record standing in for an entry that returned an error
+    fn diagnostic_for(&mut self, index: usize, error: &crate::Error) -> Option<Record> {
+        warn!(
+            "Record {} not generated from Block data with error: {}",
+            index, error
+        );
+        self.inner
+            .block_at(index as u64)
+            .ok()
+            .map(|block| Record::diagnostic(&block, Some(error.to_string())))
+    }
+
+    // Writes every remaining record to the sink, including the header
+    pub fn write_to<S: RecordSink + ?Sized>(&mut self, sink: &mut S) -> crate::Result<()> {
+        let with_diagnostics = sink.has_diagnostic_rows();
+        sink.write_header()?;
+        // Failing fast needs to see entries that failed to parse even if the sink skips them
+        while let Some((index, record)) = self.next_record(with_diagnostics || self.fail_fast) {
+            match record {
+                Ok(record) => {
+                    if self.fail_fast && record.error.is_some() {
+                        // Only entries that failed to parse into a block have a diagnostic row
+                        // with an error, the block still holds what went wrong
+                        if let Some(sample) = self.inner.block_at(index as u64)?.error {
+                            return Err(crate::Error::Reported(sample));
+                        }
+                    }
+                    let is_diagnostic =
+                        record.status != RecordStatus::Valid || record.error.is_some();
+                    if with_diagnostics || !is_diagnostic {
+                        sink.write_record(&record)?;
+                    }
+                }
+                Err(e) if self.fail_fast => return Err(e),
+                Err(e) => {
+                    if let Some(diagnostic) = self.diagnostic_for(index, &e) {
+                        if with_diagnostics {
+                            sink.write_record(&diagnostic)?;
+                        }
+                    }
+                }
+            }
+        }
+        sink.finish()
+    }
 }
 
 impl From<Parser> for Iterator {
     fn from(parser: Parser) -> Self {
+        Self::new(parser)
+    }
+}
+
+impl std::iter::Iterator for Iterator {
+    type Item = crate::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record(self.include_diagnostics)
+            .map(|(_, record)| record)
+    }
+}
+
+// String adapter over Iterator, one formatted row per record with errors as diagnostic rows
+pub struct Formatted {
+    pub records: Iterator,
+    output_type: OutputType,
+    columns: Vec<Column>,
+    header_written: bool,
+}
+
+impl Formatted {
+    pub fn new(records: Iterator, output_type: OutputType) -> Self {
+        let columns = records.inner.settings.csv_columns();
         Self {
-            inner: parser,
-            next_entry_id: 0,
-            output_type: OutputType::Csv,
+            records,
+            output_type,
+            columns,
+            header_written: false,
         }
     }
+
+    pub fn with_output_type(parser: Parser, output_type: OutputType) -> Self {
+        Self::new(Iterator::new(parser), output_type)
+    }
+
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    // Columns used by the CSV and TSV output types
+    pub fn with_columns(mut self, columns: Vec<Column>) -> Self {
+        self.columns = columns;
+        self
+    }
 }
 
-impl std::iter::Iterator for Iterator {
+impl From<Parser> for Formatted {
+    fn from(parser: Parser) -> Self {
+        Iterator::new(parser).formatted()
+    }
+}
+
+impl std::iter::Iterator for Formatted {
     type Item = String;
 
     fn next(&mut self) -> Option<Self::Item> {
-        // Loop to get blocks, allows for exclusion skips without next() recursion which causes stack overflows
-        while let Some(block) = self.inner.blocks.get(self.next_entry_id as usize).cloned() {
-            self.next_entry_id += 1;
-            let record = Record::from(&mut self.inner, &block);
-            let mut to_skip = false;
-            match record {
-                // Warn if record is Err and get next
-                Err(e) => {
-                    warn!(
-                        "Record {} not generated from Block data with error: {}",
-                        self.next_entry_id - 1,
-                        e
-                    );
-                }
-                // Exclude if exclusions regex match
-                Ok(r) => {
-                    if let Some(path_exclusion_regex) = &self.inner.settings.path_exclusion_regex {
-                        to_skip =
-                            r.path.to_str().map(|s| path_exclusion_regex.is_match(s)) == Some(true)
-                    }
-                    if let Some(filename_exclusion_regex) =
-                        &self.inner.settings.filename_exclusion_regex
-                    {
-                        to_skip = r
-                            .filename
-                            .as_ref()
-                            .map(|s| filename_exclusion_regex.is_match(s))
-                            == Some(true)
-                    }
-                    if !to_skip {
-                        return Some(self.output_type.as_type(r));
-                    }
-                }
+        if !self.header_written {
+            self.header_written = true;
+            if let Some(header) = self.output_type.header(&self.columns) {
+                return Some(header);
             }
         }
-        None
+        let with_diagnostics = self.output_type.has_diagnostic_rows();
+        loop {
+            let record = match self.records.next_record(with_diagnostics)? {
+                (_, Ok(record)) => record,
+                (index, Err(e)) => match self.records.diagnostic_for(index, &e) {
+                    Some(diagnostic) if with_diagnostics => diagnostic,
+                    _ => continue,
+                },
+            };
+            return Some(self.output_type.as_type(record, &self.columns));
+        }
     }
 }
 
@@ -158,6 +625,7 @@ mod tests {
     use std::str::FromStr;
 
     #[test]
+    #[ignore = "requires ./.test_data/mft.mft"]
     fn test_simple_record() {
         let path = PathBuf::from_str("./.test_data/mft.mft").unwrap();
         println!("{:#?}", path);
@@ -169,4 +637,306 @@ mod tests {
             println!("{:#?}", record);
         }
     }
+
+    #[test]
+    fn diagnostic_rows() {
+        let path =
+            crate::fixtures::write_temp("diagnostic_rows", &crate::iterator_tests::status_volume());
+        let parser =
+            Parser::with_settings(&path, crate::ParserSettings::new().drive_char('C')).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let rows: Vec<String> = Formatted::from(parser).collect();
+        // Zeroed entries are counted but not output
+        assert_eq!(rows.len(), 17);
+        assert!(rows[0].starts_with("entry_id,path,"));
+        assert!(rows[13].starts_with("16,C:/a.txt,") && rows[13].contains(",valid,"));
+        assert!(rows[14].starts_with("17,") && rows[14].contains(",baad,"));
+        assert!(rows[15].starts_with("18,") && rows[15].contains(",garbage,"));
+        assert!(rows[16].starts_with("19,") && rows[16].contains("Unknown attribute form code"));
+    }
+
+    #[test]
+    fn error_report() {
+        let path =
+            crate::fixtures::write_temp("error_report", &crate::iterator_tests::status_volume());
+        let settings = crate::ParserSettings::new().drive_char('C').threads(2);
+        let mut iterator = Iterator::from(Parser::with_settings(&path, settings).unwrap());
+        std::fs::remove_file(path).unwrap();
+        assert!(iterator.inner.error_report().is_empty());
+        iterator.by_ref().for_each(drop);
+        let report = iterator.inner.error_report();
+        assert_eq!(report.total, 1);
+        assert_eq!(report.corruption_count(), 1);
+        let sample = &report.samples[&crate::ErrorKind::ValueRead][0];
+        assert_eq!(
+            sample.context,
+            crate::ErrorContext {
+                entry_id: Some(19),
+                offset: Some(19 * 1024 + 0x38 + 8),
+                attribute_type: Some(0x10),
+                field: Some(String::from("form_code")),
+            }
+        );
+    }
+
+    #[test]
+    fn typed_records() {
+        use crate::fixtures::{self, Times, BASE_TIME, FLAG_DIRECTORY, FLAG_IN_USE};
+        // No $STANDARD_INFORMATION
+        let no_si = fixtures::record(
+            17,
+            1,
+            FLAG_IN_USE,
+            &[fixtures::file_name(
+                5,
+                1,
+                "b.txt",
+                1,
+                &Times::all(BASE_TIME),
+                0,
+            )],
+        );
+        let mut deleted_directory = fixtures::directory(18, 5, "old", BASE_TIME);
+        deleted_directory[22] = FLAG_DIRECTORY as u8;
+        let mft = fixtures::volume(&[
+            fixtures::file(16, 5, "a.txt", b"hello", BASE_TIME),
+            no_si,
+            deleted_directory,
+        ]);
+        let path = fixtures::write_temp("typed_records", &mft);
+        let parser =
+            Parser::with_settings(&path, crate::ParserSettings::new().drive_char('C')).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let records: Vec<crate::Result<Record>> = Iterator::from(parser).collect();
+        assert_eq!(records.len(), 15);
+        let record = records[12].as_ref().unwrap();
+        assert_eq!(record.entry_id, 16);
+        assert_eq!(record.path, PathBuf::from("C:/a.txt"));
+        assert_eq!(record.size, 5);
+        assert_eq!(record.allocated_size, 5);
+        assert_eq!(record.file_reference, 16 | 1 << 48);
+        assert_eq!(record.parent_entry_id, Some(5));
+        assert_eq!(record.parent_sequence_number, Some(1));
+        assert_eq!(record.name_space, Some(1));
+        assert_eq!(
+            (record.flags, record.link_count, record.lsn),
+            (1, 1, 0x1010)
+        );
+        assert_eq!((record.file_attributes, record.security_id), (0x20, 0x100));
+        assert_eq!(record.fn_created_raw, Some(BASE_TIME));
+        assert_eq!(record.file_names[0].changed_raw, BASE_TIME);
+        assert_eq!(Column::FnChangedRaw.value(record), BASE_TIME.to_string());
+        assert!(records[13].is_err());
+        // Deleted is the in use flag being clear, not the directory flag
+        assert!(!record.is_deleted);
+        assert!(records[14].as_ref().unwrap().is_deleted);
+        assert!(!records[11].as_ref().unwrap().is_deleted);
+    }
+
+    #[test]
+    fn parent_loops_are_orphaned() {
+        use crate::fixtures::{self, BASE_TIME};
+        // Directories that are each other's parent
+        let mft = fixtures::volume(&[
+            fixtures::directory(16, 17, "a", BASE_TIME),
+            fixtures::directory(17, 16, "b", BASE_TIME),
+        ]);
+        let path = fixtures::write_temp("parent_loops", &mft);
+        let mut parser = Parser::new(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        for (entry_id, expected) in [(16, "{Orphaned}/b/a"), (17, "{Orphaned}/a/b")] {
+            let block = parser.block_at(entry_id).unwrap();
+            let record = Record::from(&mut parser, &block).unwrap();
+            assert_eq!(record.path, PathBuf::from(expected));
+        }
+    }
+
+    #[test]
+    fn oversized_zone_identifier() {
+        use crate::fixtures::{self, Times, BASE_TIME, FLAG_IN_USE};
+        let times = Times::all(BASE_TIME);
+        let zone = b"[ZoneTransfer]\r\nZoneId=3\r\n";
+        let file = |entry: u32, zone_size: u32| {
+            let mut data = fixtures::data(Some("Zone.Identifier"), zone);
+            data[16..20].copy_from_slice(&zone_size.to_le_bytes());
+            fixtures::record(
+                entry,
+                1,
+                FLAG_IN_USE,
+                &[
+                    fixtures::standard_information(&times, 0x20),
+                    fixtures::file_name(5, 1, "a.txt", 1, &times, 0),
+                    fixtures::data(None, b""),
+                    data,
+                ],
+            )
+        };
+        let mft = fixtures::volume(&[file(16, zone.len() as u32), file(17, 0x7fff_ffff)]);
+        let path = fixtures::write_temp("oversized_zone", &mft);
+        let mut parser = Parser::new(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let block = parser.block_at(16).unwrap();
+        let record = Record::from(&mut parser, &block).unwrap();
+        assert!(record.zone_identifier.is_some());
+        // The size claims more than the attribute holds, the zone is left out
+        let block = parser.block_at(17).unwrap();
+        assert!(block.pointer(BlockType::ZoneIdentifier).is_none());
+        let record = Record::from(&mut parser, &block).unwrap();
+        assert_eq!(record.zone_identifier, None);
+    }
+
+    #[test]
+    fn inclusion_and_exclusion_lists() {
+        use crate::fixtures::{self, BASE_TIME};
+        let mft = fixtures::volume(&[
+            fixtures::directory(16, 5, "Temp", BASE_TIME),
+            fixtures::file(17, 16, "run.ps1", b"", BASE_TIME),
+            fixtures::file(18, 16, "notes.txt", b"", BASE_TIME),
+            fixtures::file(19, 5, "drop.PS1", b"", BASE_TIME),
+        ]);
+        let path = fixtures::write_temp("pattern_lists", &mft);
+        let entries = |settings: crate::ParserSettings| -> (Vec<u64>, Vec<u64>) {
+            let parser = Parser::with_settings(&path, settings.threads(2)).unwrap();
+            let mut iter = Iterator::from(parser);
+            let ids = iter.by_ref().map(|r| r.unwrap().entry_id).collect();
+            let hits = iter.inner.settings.rule_hits();
+            (ids, hits.into_iter().map(|h| h.hits).collect())
+        };
+        let settings = crate::ParserSettings::new().drive_char('C');
+        // A path exclusion is not undone by a filename exclusion that does not match
+        let (ids, hits) = entries(
+            settings
+                .clone()
+                .path_exclusion_regex("/temp/")
+                .unwrap()
+                .filename_exclusion_regex("^\\$")
+                .unwrap(),
+        );
+        assert_eq!(ids, [5, 16, 19]);
+        assert_eq!(hits, [2, 11]);
+        // Either inclusion list can include a record, exclusions still win
+        let (ids, hits) = entries(
+            settings
+                .path_inclusion_regex("^c:/temp/")
+                .unwrap()
+                .filename_inclusion_regex("\\.ps1$")
+                .unwrap()
+                .filename_exclusion_regex("^run")
+                .unwrap(),
+        );
+        assert_eq!(ids, [18, 19]);
+        assert_eq!(hits, [2, 2, 1]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn parallel_matches_sequential() {
+        use crate::fixtures::{self, BASE_TIME, SECOND};
+        let mut extra = vec![fixtures::directory(16, 5, "dir", BASE_TIME)];
+        for entry in 17..400 {
+            let parent = if entry % 3 == 0 { 16 } else { 5 };
+            let name = format!("file{}.txt", entry);
+            let time = BASE_TIME + entry as u64 * SECOND;
+            extra.push(fixtures::file(entry, parent, &name, b"data", time));
+        }
+        extra.push(crate::iterator_tests::status_volume()[16 * 1024..].to_vec());
+        let path = fixtures::write_temp("parallel", &fixtures::volume(&extra));
+        let rows = |threads: usize| -> Vec<String> {
+            let settings = crate::ParserSettings::new()
+                .drive_char('C')
+                .threads(threads);
+            Formatted::from(Parser::with_settings(&path, settings).unwrap()).collect()
+        };
+        let sequential = rows(1);
+        let parallel = rows(4);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(sequential.len(), 1 + 12 + 384 + 4);
+        assert!(sequential[13].starts_with("16,C:/dir,"));
+        assert!(sequential[15].starts_with("18,C:/dir/file18.txt,"));
+        assert_eq!(sequential, parallel);
+    }
+
+    #[test]
+    fn streaming_matches_scanned() {
+        let path =
+            crate::fixtures::write_temp("streaming", &crate::iterator_tests::status_volume());
+        let iterator = |streaming: bool, threads: usize| -> Iterator {
+            let settings = crate::ParserSettings::new()
+                .drive_char('C')
+                .streaming(streaming)
+                .threads(threads);
+            Iterator::from(Parser::with_settings(&path, settings).unwrap())
+        };
+        let rows = |iterator: Iterator| -> Vec<String> { iterator.formatted().collect() };
+        let scanned = iterator(false, 1);
+        let summary = scanned.inner.status_summary();
+        let scanned = rows(scanned);
+        let mut streamed = iterator(true, 1);
+        assert!(streamed.inner.blocks.is_empty());
+        // Nothing is read ahead of the iterator
+        assert_eq!(streamed.next().unwrap().unwrap().entry_id, 0);
+        assert_eq!(streamed.inner.status_summary().valid, 1);
+        assert_eq!(rows(iterator(true, 1)), scanned);
+        assert_eq!(rows(iterator(true, 3)), scanned);
+        let mut streamed = iterator(true, 3);
+        streamed.by_ref().for_each(drop);
+        assert_eq!(streamed.inner.status_summary(), summary);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn csv_columns() {
+        use crate::fixtures::{self, BASE_TIME};
+        let mft = fixtures::volume(&[fixtures::file(16, 5, "a \"b\", c.txt", b"hello", BASE_TIME)]);
+        let path = fixtures::write_temp("csv_columns", &mft);
+        let settings = crate::ParserSettings::new().drive_char('C');
+        let columns = vec![Column::EntryId, Column::Filename, Column::Size];
+        let rows: Vec<String> =
+            Formatted::from(Parser::with_settings(&path, settings.clone()).unwrap())
+                .with_columns(columns.clone())
+                .collect();
+        assert_eq!(rows[0], "entry_id,filename,size");
+        assert_eq!(rows[13], "16,\"a \"\"b\"\", c.txt\",5");
+        let parser = Parser::with_settings(&path, settings).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let rows: Vec<String> = Formatted::with_output_type(parser, OutputType::Tsv)
+            .with_columns(columns)
+            .collect();
+        assert_eq!(rows[0], "entry_id\tfilename\tsize");
+        assert_eq!(rows[13], "16\t\"a \"\"b\"\", c.txt\"\t5");
+    }
+
+    #[test]
+    fn bodyfile_rows() {
+        use crate::fixtures::{self, Times, BASE_TIME, FLAG_DIRECTORY, FLAG_IN_USE, SECOND};
+        // Hidden and system directory, its attributes are not just FILE_ATTRIBUTE_DIRECTORY
+        let times = Times::all(BASE_TIME);
+        let directory = fixtures::record(
+            17,
+            1,
+            FLAG_IN_USE | FLAG_DIRECTORY,
+            &[
+                fixtures::standard_information(&times, 0x16),
+                fixtures::file_name(5, 1, "System Volume Information", 1, &times, 0),
+            ],
+        );
+        let mft = fixtures::volume(&[
+            fixtures::file(16, 5, "a.txt", b"hello", BASE_TIME + 60 * SECOND),
+            directory,
+        ]);
+        let path = fixtures::write_temp("bodyfile_rows", &mft);
+        let parser =
+            Parser::with_settings(&path, crate::ParserSettings::new().drive_char('C')).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let rows: Vec<String> = Formatted::with_output_type(parser, OutputType::Bodyfile).collect();
+        let lines: Vec<&str> = rows[12].lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "0|C:/a.txt|16-128-1|r/rrwxrwxrwx|0|0|5|1672531260|1672531260|1672531260|1672531260",
+                "0|C:/a.txt ($FILE_NAME)|16-128-1|r/rrwxrwxrwx|0|0|5|1672531260|1672531260|1672531260|1672531260",
+            ]
+        );
+        assert!(rows[13].starts_with("0|C:/System Volume Information|17-128-1|d/drwxrwxrwx|"));
+    }
 }
@@ -1,9 +1,24 @@
-mod block;
+pub mod block;
+pub mod cache;
+pub mod carve;
+pub mod csv;
+pub mod diff;
 mod error;
+pub mod filter;
+#[cfg(test)]
+mod fixtures;
+pub mod input;
+pub mod pattern;
 #[macro_use]
-mod raw;
-mod attributes;
+pub mod raw;
+pub mod attributes;
 mod iter;
+pub mod sink;
+pub mod stream;
+pub mod timeline;
+pub mod timestomp;
+pub mod tree;
+pub mod usn;
 
 #[macro_use]
 extern crate serde;
@@ -11,27 +26,51 @@ extern crate serde;
 extern crate log;
 
 use block::BlockType;
+use raw::RecordStatus;
 
 use crate::block::{Block, SectionPointer};
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::{BufReader, Read, Seek, SeekFrom};
+use crate::cache::PathCache;
+use crate::input::Input;
+use crate::pattern::{PatternSet, Rule, RuleHits};
+use crate::tree::DirectoryTree;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::io::{Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
 
 pub type Result<T> = std::result::Result<T, error::Error>;
-pub use crate::error::Error;
-pub use iter::Iterator;
+pub use crate::error::{Error, ErrorContext, ErrorKind, ErrorReport, ErrorSample};
+pub use iter::{Formatted, Iterator, Record};
+pub use sink::{OutputType, RecordSink};
 
 pub const MFT_RECORD_SIZE: u64 = 1024;
+// Path parts kept in memory when streaming, see cache::PathCache::bounded
+pub const STREAMING_PATH_CACHE_SIZE: usize = 1 << 16;
 
 #[derive(Debug)]
 // Iterates over the MFT file and returns sizes and offsets for useful data by entry
 pub struct Parser {
-    pub reader: BufReader<File>,
+    pub path: PathBuf,
+    pub reader: Input,
     pub size: u64,
     pub records: u64,
-    pub blocks: Vec<Block>,
-    pub path_parts: HashMap<u64, Option<(String, u64)>>, // Entry ID and (Path Part, Entry)
+    // Size of the first record, every entry is at entry_id * record_size
+    pub record_size: u64,
+    // Shared with worker parsers, see Parser::worker. Empty when streaming
+    pub blocks: Arc<Vec<Block>>,
+    pub path_parts: Arc<PathCache>,
+    // Built on the first path lookup, shared with worker parsers
+    directory_tree: Arc<OnceLock<DirectoryTree>>,
+    // Status counts of the blocks iterated so far, only used when streaming
+    streamed: Arc<Mutex<StatusSummary>>,
+    // Errors for the entries iterated so far, shared with worker parsers
+    errors: Arc<Mutex<ErrorReport>>,
+    // Reused for each record read by get_best_path_part, fixups are applied in place
+    scratch: Vec<u8>,
+    // Entry decoded by Record::from, kept apart from scratch which path lookups overwrite
+    entry_bytes: Vec<u8>,
+    pub timestomp: timestomp::Analyzer,
     //
     pub settings: ParserSettings,
 }
@@ -41,6 +80,15 @@ impl Parser {
         Self::with_settings(path, ParserSettings::default())
     }
 
+    // Default settings apart from the drive letter paths start with
+    pub fn from_path<P: AsRef<Path>>(path: P, drive_char: Option<char>) -> crate::Result<Self> {
+        let settings = ParserSettings {
+            drive_char,
+            ..ParserSettings::default()
+        };
+        Self::with_settings(path, settings)
+    }
+
     pub fn with_settings<P: AsRef<Path>>(path: P, settings: ParserSettings) -> crate::Result<Self> {
         trace!(
             "Creating MftParser struct from {} ({} drive)",
@@ -50,56 +98,261 @@ impl Parser {
                 .map(|c| c.to_string())
                 .unwrap_or_else(|| String::from("Unknown"))
         );
-        // Get reader
-        let file = File::open(path)?;
-        let mut reader = BufReader::new(file);
+        let reader = Input::open(&path, settings.mmap)?;
+        Self::with_input(path, reader, settings)
+    }
+
+    // Parser over an MFT that is not in a file, e.g. stdin, the input is read into memory
+    pub fn from_reader<R: Read>(reader: R, settings: ParserSettings) -> crate::Result<Self> {
+        Self::with_input("-", Input::from_reader(reader)?, settings)
+    }
+
+    // Parser over an already opened input, path is used to open more readers for worker threads
+    pub fn with_input<P: AsRef<Path>>(
+        path: P,
+        mut reader: Input,
+        settings: ParserSettings,
+    ) -> crate::Result<Self> {
+        let path = path.as_ref().to_path_buf();
         // Get size
-        let size = reader.get_ref().metadata()?.len();
-        // Get records
-        let records = size / MFT_RECORD_SIZE;
-        // Get Blocks
-        let blocks = Self::get_blocks(&mut reader, records)?;
+        let size = reader.len()?;
+        // Every record has the size of the first one, unless it cannot be read
+        let record_size = raw::Header::from_reader(&mut reader)
+            .map(|h| h.record_size())
+            .unwrap_or(MFT_RECORD_SIZE);
+        let records = size / record_size;
+        // Get Blocks, when streaming they are read as the Iterator reaches them
+        let blocks = match (settings.streaming, settings.threads > 1) {
+            (true, _) => Vec::new(),
+            (false, true) => {
+                Self::get_blocks_parallel(&reader, &path, size, record_size, settings.threads)?
+            }
+            (false, false) => Self::get_blocks(&mut reader, size, record_size)?,
+        };
+        let path_parts = match settings.streaming {
+            true => PathCache::bounded(STREAMING_PATH_CACHE_SIZE),
+            false => PathCache::new(),
+        };
         // Return
-        trace!("Returning MftParser parser struct");
-        Ok(Self {
+        let mut parser = Self {
+            path,
             reader,
             size,
             records,
-            blocks,
-            path_parts: HashMap::new(),
+            record_size,
+            blocks: Arc::new(blocks),
+            path_parts: Arc::new(path_parts),
+            directory_tree: Arc::default(),
+            streamed: Arc::default(),
+            errors: Arc::default(),
+            scratch: Vec::new(),
+            entry_bytes: Vec::new(),
+            timestomp: timestomp::Analyzer::default(),
             settings,
+        };
+        // The $MFT is created when the volume is formatted
+        let volume_created = parser.get_best_path_part(0).ok().map(|f| f.creation_time);
+        parser.timestomp = timestomp::Analyzer::new(volume_created);
+        trace!("Returning MftParser parser struct");
+        Ok(parser)
+    }
+
+    // Parser over the same file with its own reader, sharing blocks and the path cache
+    pub fn worker(&self) -> crate::Result<Self> {
+        Ok(Self {
+            path: self.path.clone(),
+            reader: self.reader.try_clone(&self.path)?,
+            size: self.size,
+            records: self.records,
+            record_size: self.record_size,
+            blocks: Arc::clone(&self.blocks),
+            path_parts: Arc::clone(&self.path_parts),
+            directory_tree: Arc::clone(&self.directory_tree),
+            streamed: Arc::clone(&self.streamed),
+            errors: Arc::clone(&self.errors),
+            scratch: Vec::new(),
+            entry_bytes: Vec::new(),
+            timestomp: timestomp::Analyzer::new(self.timestomp.volume_created),
+            settings: self.settings.clone(),
+        })
+    }
+
+    fn get_blocks(reader: &mut Input, size: u64, record_size: u64) -> crate::Result<Vec<Block>> {
+        Self::get_blocks_range(reader, 0, size, 0, record_size)
+    }
+
+    // Splits the file into one range of whole records per thread, blocks are returned in entry order
+    fn get_blocks_parallel(
+        input: &Input,
+        path: &Path,
+        size: u64,
+        record_size: u64,
+        threads: usize,
+    ) -> crate::Result<Vec<Block>> {
+        let records_per_thread = (size / record_size).div_ceil(threads as u64).max(1);
+        trace!(
+            "Getting blocks from MFT file ({} bytes) on {} threads",
+            size,
+            threads
+        );
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..threads as u64)
+                .map(|i| i * records_per_thread)
+                .filter(|first_record| first_record * record_size < size)
+                .map(|first_record| {
+                    let start = first_record * record_size;
+                    let end = (start + records_per_thread * record_size).min(size);
+                    scope.spawn(move || {
+                        let mut reader = input.try_clone(path)?;
+                        Self::get_blocks_range(&mut reader, start, end, first_record, record_size)
+                    })
+                })
+                .collect();
+            let mut blocks = Vec::new();
+            for handle in handles {
+                match handle.join() {
+                    Ok(range) => blocks.extend(range?),
+                    Err(_) => {
+                        return Err(Error::Internal(String::from(
+                            "Block worker thread panicked",
+                        )))
+                    }
+                }
+            }
+            Ok(blocks)
         })
     }
 
-    fn get_blocks<R: Read + Seek>(reader: &mut R, records: u64) -> crate::Result<Vec<Block>> {
-        trace!("Getting blocks from MFT file ({} records)", records);
-        let mut prev = None;
+    fn get_blocks_range(
+        reader: &mut Input,
+        start: u64,
+        end: u64,
+        first_record: u64,
+        record_size: u64,
+    ) -> crate::Result<Vec<Block>> {
+        trace!("Getting blocks from MFT file (bytes {} to {})", start, end);
+        let mut offset = start;
         let mut blocks = Vec::new();
-        for record_n in 0..(records) {
-            let entry: raw::Entry = raw::Entry::from_reader(reader, prev)?;
-            let block = block::Block::new_with_entry(reader, &entry, record_n)?;
+        let mut record_n = first_record;
+        let mut buffer = Vec::new();
+        // Stepping by the size in each header would let one zeroed or garbage record shift every
+        // later entry, so every mode uses the size of the first record
+        while offset + record_size <= end {
+            let block = Self::read_block(reader, &mut buffer, offset, record_n)?;
+            offset += record_size;
             blocks.push(block);
-            prev = Some(entry);
+            record_n += 1;
         }
         Ok(blocks)
     }
 
-    // Debug function for caching all path parts
-    pub fn fill_path_parts_cache(&mut self) -> crate::Result<()> {
-        for i in 0..self.blocks.len() {
-            let entry_id = self.blocks.get(i).map(|b| b.entry_id);
-            if let Some(id) = entry_id {
-                let filename_attribute = match self.get_best_path_part(id) {
-                    Ok(f) => Some(f),
-                    Err(Error::MissingFileNameAttribute) => None,
-                    Err(e) => return Err(e),
-                };
-                self.path_parts.insert(
-                    id,
-                    filename_attribute.map(|f| (f.name, f.parent_file_reference.entry)),
+    // Block for the record at offset, buffer is reused for the entry bytes
+    fn read_block(
+        reader: &mut Input,
+        buffer: &mut Vec<u8>,
+        offset: u64,
+        record_n: u64,
+    ) -> crate::Result<Block> {
+        let entry = reader
+            .read_entry_bytes(offset, buffer)
+            .map_err(|e| e.for_entry(record_n))
+            .and_then(|()| raw::Entry::from_entry_bytes(buffer, offset, record_n));
+        match entry {
+            Ok(entry) => block::Block::new_with_entry(reader, &entry, record_n),
+            // Unparseable entries are kept so they can be reported rather than ending the run
+            Err(e) => {
+                warn!(
+                    "Entry {} at offset {} could not be parsed: {}",
+                    record_n, offset, e
                 );
+                let status = raw::Header::from_reader(&mut std::io::Cursor::new(buffer.as_slice()))
+                    .map(|h| h.status())
+                    .unwrap_or(RecordStatus::Garbage);
+                Ok(block::Block::new_with_error(offset, record_n, status, &e))
             }
         }
+    }
+
+    // Number of entries, when streaming this is worked out from the file size
+    pub fn block_count(&self) -> u64 {
+        match self.settings.streaming {
+            true => self.size / self.record_size,
+            false => self.blocks.len() as u64,
+        }
+    }
+
+    // Block for an entry, read from the input when streaming
+    pub fn block_at(&mut self, entry_id: u64) -> crate::Result<Block> {
+        match self.settings.streaming {
+            true => Self::read_block(
+                &mut self.reader,
+                &mut self.scratch,
+                entry_id * self.record_size,
+                entry_id,
+            ),
+            false => self
+                .blocks
+                .get(entry_id as usize)
+                .cloned()
+                .ok_or_else(|| crate::Error::missing_block("Block", entry_id)),
+        }
+    }
+
+    // Raw entry with every attribute header, for when the block pointers are not enough
+    pub fn entry(&mut self, entry_id: u64) -> crate::Result<raw::Entry> {
+        let offset = entry_id * self.record_size;
+        self.reader
+            .read_entry_bytes(offset, &mut self.scratch)
+            .map_err(|e| e.for_entry(entry_id))?;
+        raw::Entry::from_entry_bytes(&self.scratch, offset, entry_id)
+    }
+
+    // Counts a block reached by the Iterator towards the streaming status summary
+    fn record_streamed(&self, block: &Block) {
+        if self.settings.streaming {
+            let mut streamed = self.streamed.lock().unwrap_or_else(PoisonError::into_inner);
+            streamed.add(block);
+        }
+    }
+
+    // Counts an error for an entry reached by the Iterator towards the error report
+    fn record_error(&self, sample: ErrorSample) {
+        let mut errors = self.errors.lock().unwrap_or_else(PoisonError::into_inner);
+        errors.add(sample);
+    }
+
+    // Errors for the entries iterated so far, including entries that failed to parse into a block
+    pub fn error_report(&self) -> ErrorReport {
+        let errors = self.errors.lock().unwrap_or_else(PoisonError::into_inner);
+        errors.clone()
+    }
+
+    // When streaming only the entries iterated so far are counted
+    pub fn status_summary(&self) -> StatusSummary {
+        if self.settings.streaming {
+            let streamed = self.streamed.lock().unwrap_or_else(PoisonError::into_inner);
+            return streamed.clone();
+        }
+        let mut summary = StatusSummary::default();
+        for block in self.blocks.iter() {
+            summary.add(block);
+        }
+        summary
+    }
+
+    // Debug function for caching all path parts
+    pub fn fill_path_parts_cache(&mut self) -> crate::Result<()> {
+        for id in 0..self.block_count() {
+            let filename_attribute = match self.get_best_path_part(id) {
+                Ok(f) => Some(f),
+                Err(Error::MissingFileNameAttribute) => None,
+                Err(e) => return Err(e),
+            };
+            self.path_parts.insert(
+                id,
+                filename_attribute.map(|f| (f.name, f.parent_file_reference.entry)),
+            );
+        }
         Ok(())
     }
 
@@ -107,11 +360,13 @@ impl Parser {
         trace!("Getting path for entry {}", entry_id);
         let mut parts = Vec::new();
         let mut current_id = entry_id;
+        // Parent links can loop through several entries in a corrupt or reused MFT
+        let mut visited = HashSet::from([entry_id]);
         loop {
-            match self.path_parts.get(&current_id) {
+            match self.path_parts.get(current_id) {
                 // 5 is a reserved reference for the root of the filesystem
                 Some(Some((name, 5))) => {
-                    parts.push(name.clone());
+                    parts.push(name);
                     if let Some(drive) = self.settings.drive_char {
                         parts.push(format!("{}:", drive));
                     } else {
@@ -120,12 +375,12 @@ impl Parser {
                     break;
                 }
                 Some(Some((name, parent_id))) => {
-                    parts.push(name.clone());
-                    if current_id == *parent_id || *parent_id == 0 {
+                    parts.push(name);
+                    if parent_id == 0 || !visited.insert(parent_id) {
                         parts.push("{Orphaned}".to_string());
                         break;
                     }
-                    current_id = *parent_id;
+                    current_id = parent_id;
                 }
                 // If part of the path for this entry has not yet been resolved and cached, get the best one
                 _ => match self.get_best_path_part(current_id) {
@@ -147,21 +402,34 @@ impl Parser {
         ))
     }
 
+    // Tree of every entry, built with one pass over the MFT the first time it is needed
+    pub fn directory_tree(&mut self) -> &DirectoryTree {
+        if self.directory_tree.get().is_none() {
+            let tree = DirectoryTree::build(self);
+            let _ = self.directory_tree.set(tree);
+        }
+        self.directory_tree.get_or_init(DirectoryTree::default)
+    }
+
+    // Entries with a path from another artifact, see DirectoryTree::lookup
+    pub fn lookup_path(&mut self, path: &str) -> Vec<u64> {
+        self.directory_tree().lookup(path)
+    }
+
     pub fn get_best_path_part(&mut self, entry_id: u64) -> crate::Result<attributes::FileName> {
-        fn recurse_attributes<R: Read + Seek>(
-            file_reader: &mut R,
+        fn recurse_attributes(
+            file_reader: &mut Input,
+            scratch: &mut Vec<u8>,
             target_block: &Block,
             target_attribute: Option<SectionPointer>,
-            blocks: &[Block],
+            lookup: &BlockLookup,
         ) -> crate::Result<attributes::FileName> {
             // Grab entry block, there will be only one per block
             let entry_block = target_block
-                .blocks
-                .iter()
-                .find(|block| block.block_type == BlockType::Entry)
+                .pointer(BlockType::Entry)
                 .ok_or_else(|| crate::Error::missing_block("EntryBlock", target_block.entry_id))?;
-            let entry_bytes = crate::raw::Entry::get_entry_bytes(file_reader, entry_block.offset)?;
-            let mut block_reader = std::io::Cursor::new(entry_bytes);
+            file_reader.read_entry_bytes(entry_block.offset, scratch)?;
+            let mut block_reader = std::io::Cursor::new(scratch.as_slice());
             // Get all relevant attribute blocks (FileName and AttributeList)
             let attribute_blocks = target_block
                 .blocks
@@ -185,20 +453,38 @@ impl Parser {
                     BlockType::FileName => {
                         // Seek relative offset
                         let filename_attribute =
-                            attributes::FileName::from_reader(&mut block_reader)?;
+                            attributes::FileName::from_reader(&mut block_reader).map_err(|e| {
+                                e.in_attribute(0x30)
+                                    .in_entry(target_block.entry_id, entry_block.offset)
+                            })?;
                         if filename_attribute.name_space != 2 {
                             filename_opt = Some(filename_attribute);
                             break 'outer;
                         }
                     }
                     BlockType::AttributeList => {
+                        let attribute_list =
+                            attributes::AttributeList::from_reader(&mut block_reader, block.size)
+                                .map_err(|e| {
+                                e.in_attribute(0x20)
+                                    .in_entry(target_block.entry_id, entry_block.offset)
+                            })?;
+                        // Only the entries the list points to, sorted so block::find_block works
+                        let mut entry_ids: Vec<u64> = attribute_list
+                            .0
+                            .iter()
+                            .map(|item| item.file_reference.entry)
+                            .collect();
+                        entry_ids.sort_unstable();
+                        entry_ids.dedup();
+                        let mut blocks = Vec::with_capacity(entry_ids.len());
+                        for entry_id in entry_ids {
+                            blocks.push(lookup.get(file_reader, entry_id)?.into_owned());
+                        }
                         for (resolved_entry_id, resolved_attribute) in
-                            attributes::AttributeList::from_reader(&mut block_reader, block.size)?
-                                .resolve_to_blocks(blocks)
+                            attribute_list.resolve_to_blocks(&blocks)?
                         {
-                            let resolved_entry = blocks
-                                .iter()
-                                .find(|b| b.entry_id == resolved_entry_id)
+                            let resolved_entry = block::find_block(&blocks, resolved_entry_id)
                                 .ok_or_else(|| {
                                     crate::Error::missing_block(
                                         "AttributePointer",
@@ -206,11 +492,13 @@ impl Parser {
                                     )
                                 })?;
                             // Recurse
+                            // The scratch buffer still holds this entry, extension records get their own
                             let attribute_opt = recurse_attributes(
                                 file_reader,
+                                &mut Vec::new(),
                                 resolved_entry,
                                 Some(resolved_attribute),
-                                blocks,
+                                lookup,
                             )
                             .ok();
                             if attribute_opt.is_some() {
@@ -228,20 +516,95 @@ impl Parser {
             }
         }
         trace!("Getting best path part for entry {}", entry_id);
-        let target_block = self
-            .blocks
-            .iter()
-            .find(|b| b.entry_id == entry_id)
-            .ok_or_else(|| crate::Error::missing_block("Block", entry_id))?;
-        recurse_attributes(&mut self.reader, target_block, None, &self.blocks)
+        let lookup = BlockLookup {
+            blocks: &self.blocks,
+            record_size: self.settings.streaming.then_some(self.record_size),
+        };
+        let target_block = lookup.get(&mut self.reader, entry_id)?;
+        recurse_attributes(
+            &mut self.reader,
+            &mut self.scratch,
+            &target_block,
+            None,
+            &lookup,
+        )
     }
 }
 
-#[derive(Debug, Default)]
+// Blocks needed while resolving a path part, scanned up front or read on demand when streaming
+struct BlockLookup<'a> {
+    blocks: &'a [Block],
+    record_size: Option<u64>,
+}
+
+impl<'a> BlockLookup<'a> {
+    fn get(&self, reader: &mut Input, entry_id: u64) -> crate::Result<Cow<'a, Block>> {
+        match self.record_size {
+            Some(record_size) => {
+                Parser::read_block(reader, &mut Vec::new(), entry_id * record_size, entry_id)
+                    .map(Cow::Owned)
+            }
+            None => block::find_block(self.blocks, entry_id)
+                .map(Cow::Borrowed)
+                .ok_or_else(|| crate::Error::missing_block("Block", entry_id)),
+        }
+    }
+}
+
+// Record status counts for the whole MFT
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct StatusSummary {
+    pub valid: u64,
+    pub baad: u64,
+    pub zeroed: u64,
+    pub garbage: u64,
+    pub unparseable: u64,
+}
+
+impl StatusSummary {
+    pub fn add(&mut self, block: &Block) {
+        match block.status {
+            RecordStatus::Valid => self.valid += 1,
+            RecordStatus::Baad => self.baad += 1,
+            RecordStatus::Zeroed => self.zeroed += 1,
+            RecordStatus::Garbage => self.garbage += 1,
+        }
+        if block.error.is_some() {
+            self.unparseable += 1;
+        }
+    }
+}
+
+impl std::fmt::Display for StatusSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} valid, {} baad, {} zeroed, {} garbage, {} unparseable",
+            self.valid, self.baad, self.zeroed, self.garbage, self.unparseable
+        )
+    }
+}
+
+#[derive(Debug, Default, Clone)]
 pub struct ParserSettings {
     pub drive_char: Option<char>,
-    pub path_exclusion_regex: Option<regex::Regex>,
-    pub filename_exclusion_regex: Option<regex::Regex>,
+    // Case insensitive pattern lists, see ParserSettings::is_included for how they combine
+    pub path_inclusions: Option<PatternSet>,
+    pub path_exclusions: Option<PatternSet>,
+    pub filename_inclusions: Option<PatternSet>,
+    pub filename_exclusions: Option<PatternSet>,
+    // Only records matching the filter are iterated, see filter::Filter
+    pub filter: Option<filter::Filter>,
+    pub scan_slack: bool,
+    // Read the MFT through a memory mapping instead of a buffered file (mmap feature)
+    pub mmap: bool,
+    // Parse records as they are iterated instead of scanning every block up front
+    pub streaming: bool,
+    pub output_type: OutputType,
+    // Worker threads for decoding, 1 or less decodes everything on the calling thread
+    pub threads: usize,
+    // CSV and TSV columns, None uses csv::Column::DEFAULT
+    pub columns: Option<Vec<csv::Column>>,
 }
 
 impl ParserSettings {
@@ -254,27 +617,268 @@ impl ParserSettings {
         self
     }
 
-    pub fn path_exclusion_regex(mut self, regex: &str) -> Self {
-        self.path_exclusion_regex = Some(
-            regex::Regex::new(regex).expect("Unable to parse regex provided for path exclusions"),
-        );
+    // Each regex or file adds rules to the list, a record matching any of them matches the list
+    pub fn path_inclusion_regex(mut self, regex: &str) -> crate::Result<Self> {
+        self.path_inclusions = add_rules(
+            "Path inclusion",
+            self.path_inclusions,
+            vec![Rule::new(regex)],
+        )?;
+        Ok(self)
+    }
+
+    pub fn path_exclusion_regex(mut self, regex: &str) -> crate::Result<Self> {
+        self.path_exclusions = add_rules(
+            "Path exclusion",
+            self.path_exclusions,
+            vec![Rule::new(regex)],
+        )?;
+        Ok(self)
+    }
+
+    pub fn filename_inclusion_regex(mut self, regex: &str) -> crate::Result<Self> {
+        self.filename_inclusions = add_rules(
+            "Filename inclusion",
+            self.filename_inclusions,
+            vec![Rule::new(regex)],
+        )?;
+        Ok(self)
+    }
+
+    pub fn filename_exclusion_regex(mut self, regex: &str) -> crate::Result<Self> {
+        self.filename_exclusions = add_rules(
+            "Filename exclusion",
+            self.filename_exclusions,
+            vec![Rule::new(regex)],
+        )?;
+        Ok(self)
+    }
+
+    // Pattern files hold one regex per line, see pattern::Rule::from_file
+    pub fn path_inclusion_file<P: AsRef<Path>>(mut self, path: P) -> crate::Result<Self> {
+        self.path_inclusions = add_rules(
+            "Path inclusion",
+            self.path_inclusions,
+            Rule::from_file(path)?,
+        )?;
+        Ok(self)
+    }
+
+    pub fn path_exclusion_file<P: AsRef<Path>>(mut self, path: P) -> crate::Result<Self> {
+        self.path_exclusions = add_rules(
+            "Path exclusion",
+            self.path_exclusions,
+            Rule::from_file(path)?,
+        )?;
+        Ok(self)
+    }
+
+    pub fn filename_inclusion_file<P: AsRef<Path>>(mut self, path: P) -> crate::Result<Self> {
+        self.filename_inclusions = add_rules(
+            "Filename inclusion",
+            self.filename_inclusions,
+            Rule::from_file(path)?,
+        )?;
+        Ok(self)
+    }
+
+    pub fn filename_exclusion_file<P: AsRef<Path>>(mut self, path: P) -> crate::Result<Self> {
+        self.filename_exclusions = add_rules(
+            "Filename exclusion",
+            self.filename_exclusions,
+            Rule::from_file(path)?,
+        )?;
+        Ok(self)
+    }
+
+    pub fn filter(mut self, expression: &str) -> crate::Result<Self> {
+        self.filter = Some(filter::Filter::parse(expression)?);
+        Ok(self)
+    }
+
+    pub fn scan_slack(mut self, scan_slack: bool) -> Self {
+        self.scan_slack = scan_slack;
         self
     }
 
-    pub fn filename_exclusion_regex(mut self, regex: &str) -> Self {
-        self.filename_exclusion_regex = Some(
-            regex::Regex::new(regex)
-                .expect("Unable to parse regex provided for filename exclusions"),
-        );
+    pub fn streaming(mut self, streaming: bool) -> Self {
+        self.streaming = streaming;
+        self
+    }
+
+    pub fn mmap(mut self, mmap: bool) -> Self {
+        self.mmap = mmap;
         self
     }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    pub fn output_type(mut self, output_type: OutputType) -> Self {
+        self.output_type = output_type;
+        self
+    }
+
+    pub fn columns(mut self, columns: Vec<csv::Column>) -> Self {
+        self.columns = Some(columns);
+        self
+    }
+
+    // Exclusions win over inclusions. With inclusions set a record has to match the path or the
+    // filename inclusions, with neither set everything not excluded is included. Every list is
+    // checked so each rule's hits do not depend on the other lists
+    pub fn is_included(&self, path: Option<&str>, filename: Option<&str>) -> bool {
+        let matches = |set: &Option<PatternSet>, value: Option<&str>| {
+            set.as_ref()
+                .map(|set| value.is_some_and(|v| set.is_match(v)))
+        };
+        let path_excluded = matches(&self.path_exclusions, path) == Some(true);
+        let filename_excluded = matches(&self.filename_exclusions, filename) == Some(true);
+        let included = match (
+            matches(&self.path_inclusions, path),
+            matches(&self.filename_inclusions, filename),
+        ) {
+            (None, None) => true,
+            (path, filename) => path == Some(true) || filename == Some(true),
+        };
+        !path_excluded && !filename_excluded && included
+    }
+
+    // Hits of every rule in the pattern lists so far, shared with worker parsers
+    pub fn rule_hits(&self) -> Vec<RuleHits> {
+        [
+            ("path inclusion", &self.path_inclusions),
+            ("path exclusion", &self.path_exclusions),
+            ("filename inclusion", &self.filename_inclusions),
+            ("filename exclusion", &self.filename_exclusions),
+        ]
+        .into_iter()
+        .flat_map(|(list, set)| {
+            set.iter()
+                .flat_map(|set| set.hits())
+                .map(move |(rule, hits)| RuleHits { list, rule, hits })
+        })
+        .collect()
+    }
+
+    // Configured columns, or the defaults with slack appended when it is scanned
+    pub fn csv_columns(&self) -> Vec<csv::Column> {
+        self.columns.clone().unwrap_or_else(|| {
+            let mut columns = csv::Column::DEFAULT.to_vec();
+            if self.scan_slack {
+                columns.push(csv::Column::Slack);
+            }
+            columns
+        })
+    }
+}
+
+// Adds rules to a pattern list, errors name the list
+fn add_rules(
+    list: &str,
+    set: Option<PatternSet>,
+    rules: Vec<Rule>,
+) -> crate::Result<Option<PatternSet>> {
+    match set {
+        Some(set) => set.extend(rules),
+        None => PatternSet::new(rules),
+    }
+    .map(Some)
+    .map_err(|e| match e {
+        Error::InvalidSetting(reason) => Error::InvalidSetting(format!("{} {}", list, reason)),
+        e => e,
+    })
 }
 
 #[cfg(test)]
 mod iterator_tests {
-    use super::Parser;
+    use super::{fixtures, Input, Parser, ParserSettings, StatusSummary};
     use std::{path::PathBuf, str::FromStr};
+
+    // Volume with one of each record status after the system files
+    pub(crate) fn status_volume() -> Vec<u8> {
+        let mut baad = fixtures::file(17, 5, "baad.txt", b"", fixtures::BASE_TIME);
+        baad[0..4].copy_from_slice(b"BAAD");
+        let garbage: Vec<u8> = (0..1024).map(|i| (i * 13 % 251) as u8).collect();
+        let mut bad_form = fixtures::file(19, 5, "bad_form.txt", b"", fixtures::BASE_TIME);
+        bad_form[0x38 + 8] = 5;
+        fixtures::volume(&[
+            fixtures::file(16, 5, "a.txt", b"hello", fixtures::BASE_TIME),
+            baad,
+            garbage,
+            bad_form,
+            vec![0; 1024],
+        ])
+    }
+
+    #[test]
+    fn status_summary() {
+        let mft = status_volume();
+        let blocks = Parser::get_blocks(
+            &mut Input::from_reader(mft.as_slice()).unwrap(),
+            mft.len() as u64,
+            1024,
+        )
+        .unwrap();
+        assert_eq!(blocks.len(), 21);
+        let path = fixtures::write_temp("status_summary", &mft);
+        let parser = Parser::new(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(
+            parser.status_summary(),
+            StatusSummary {
+                valid: 14,
+                baad: 1,
+                zeroed: 5,
+                garbage: 1,
+                unparseable: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn large_records_keep_entry_numbers() {
+        use fixtures::{Times, BASE_TIME, FLAG_IN_USE};
+        let times = Times::all(BASE_TIME);
+        let file = |entry: u32, name: &str| {
+            fixtures::sized_record(
+                4096,
+                entry,
+                1,
+                FLAG_IN_USE,
+                &[
+                    fixtures::standard_information(&times, 0x20),
+                    fixtures::file_name(5, 1, name, 1, &times, 0),
+                ],
+            )
+        };
+        // A zeroed and a garbage record used to be read as four 1024 byte records each
+        let mut mft = file(0, "$MFT");
+        mft.extend(vec![0; 4096]);
+        mft.extend((0..4096).map(|i| (i * 13 % 251) as u8));
+        mft.extend(file(3, "c.txt"));
+        let path = fixtures::write_temp("large_records", &mft);
+        for settings in [
+            ParserSettings::new(),
+            ParserSettings::new().threads(2),
+            ParserSettings::new().streaming(true),
+        ] {
+            let mut parser = Parser::with_settings(&path, settings).unwrap();
+            assert_eq!((parser.records, parser.block_count()), (4, 4));
+            let block = parser.block_at(3).unwrap();
+            assert_eq!(block.status, super::RecordStatus::Valid);
+            assert_eq!(
+                parser.get_file_path(3).unwrap(),
+                PathBuf::from("{Root}/c.txt")
+            );
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
     #[test]
+    #[ignore = "requires ./.test_data/mft.mft"]
     fn create_iterator() {
         let path = PathBuf::from_str("./.test_data/mft.mft").unwrap();
         println!("{:#?}", path);
@@ -0,0 +1,150 @@
+use regex::{RegexSet, RegexSetBuilder};
+use std::fmt;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+// A regex in a pattern list and where it came from, a file and line or "argument"
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Rule {
+    pub pattern: String,
+    pub source: String,
+}
+
+impl Rule {
+    pub fn new(pattern: &str) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            source: String::from("argument"),
+        }
+    }
+
+    // One regex per line, blank lines and lines starting with # are skipped
+    pub fn from_file<P: AsRef<Path>>(path: P) -> crate::Result<Vec<Self>> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            crate::Error::InvalidSetting(format!("Pattern file {}: {}", path.display(), e))
+        })?;
+        let rules: Vec<Self> = contents
+            .lines()
+            .enumerate()
+            .map(|(n, line)| (n, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(n, line)| Self {
+                pattern: line.to_string(),
+                source: format!("{}:{}", path.display(), n + 1),
+            })
+            .collect();
+        // An empty inclusion list would skip every record, which is never what was meant
+        match rules.is_empty() {
+            true => Err(crate::Error::InvalidSetting(format!(
+                "Pattern file {} has no patterns",
+                path.display()
+            ))),
+            false => Ok(rules),
+        }
+    }
+}
+
+// Case insensitive rules matched together as a RegexSet. Hits are counted per rule and shared by
+// clones, so worker parsers add to the same counts
+#[derive(Debug, Clone)]
+pub struct PatternSet {
+    pub rules: Vec<Rule>,
+    set: RegexSet,
+    hits: Arc<Vec<AtomicU64>>,
+}
+
+impl PatternSet {
+    pub fn new(rules: Vec<Rule>) -> crate::Result<Self> {
+        // Compiled one at a time first so the error names the rule
+        for rule in &rules {
+            RegexSetBuilder::new([&rule.pattern])
+                .case_insensitive(true)
+                .build()
+                .map_err(|e| {
+                    crate::Error::InvalidSetting(format!(
+                        "regex {} ({}): {}",
+                        rule.pattern, rule.source, e
+                    ))
+                })?;
+        }
+        let set = RegexSetBuilder::new(rules.iter().map(|r| &r.pattern))
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| crate::Error::InvalidSetting(format!("regex set: {}", e)))?;
+        let hits = Arc::new(rules.iter().map(|_| AtomicU64::new(0)).collect());
+        Ok(Self { rules, set, hits })
+    }
+
+    // Set with the rules of both, hit counts start again at 0
+    pub fn extend(self, rules: Vec<Rule>) -> crate::Result<Self> {
+        let mut all = self.rules;
+        all.extend(rules);
+        Self::new(all)
+    }
+
+    // Counts a hit for every rule that matches, not only the first
+    pub fn is_match(&self, value: &str) -> bool {
+        let matches = self.set.matches(value);
+        for i in matches.iter() {
+            self.hits[i].fetch_add(1, Ordering::Relaxed);
+        }
+        matches.matched_any()
+    }
+
+    pub fn hits(&self) -> Vec<(Rule, u64)> {
+        self.rules
+            .iter()
+            .cloned()
+            .zip(self.hits.iter().map(|h| h.load(Ordering::Relaxed)))
+            .collect()
+    }
+}
+
+// Hits of one rule in one of the ParserSettings pattern lists
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RuleHits {
+    pub list: &'static str,
+    pub rule: Rule,
+    pub hits: u64,
+}
+
+impl fmt::Display for RuleHits {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} ({}): {}",
+            self.list, self.rule.pattern, self.rule.source, self.hits
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pattern_files_and_hits() {
+        let path = std::env::temp_dir().join(format!("mft-{}-patterns.txt", std::process::id()));
+        std::fs::write(&path, "# IOC paths\n\\\\temp\\\\\n\n  \\.ps1$\n").unwrap();
+        let rules = Rule::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[1].source, format!("{}:4", path.display()));
+        let set = PatternSet::new(rules)
+            .unwrap()
+            .extend(vec![Rule::new("^c:/")])
+            .unwrap();
+        let clone = set.clone();
+        assert!(set.is_match("C:\\Temp\\RUN.PS1"));
+        assert!(clone.is_match("c:/users/run.ps1"));
+        assert!(!set.is_match("d:/notes.txt"));
+        let hits: Vec<u64> = set.hits().into_iter().map(|(_, hits)| hits).collect();
+        assert_eq!(hits, [1, 2, 1]);
+
+        let error = PatternSet::new(vec![Rule::new("ok"), Rule::new("(")]).unwrap_err();
+        assert!(error.to_string().contains("regex ( (argument)"));
+        assert!(Rule::from_file(&path).is_err());
+    }
+}
@@ -1,18 +1,31 @@
 use crate::error::Error;
 use byteorder::{ByteOrder, LittleEndian, ReadBytesExt};
+use chrono::{DateTime, Utc};
+use std::fmt;
 use std::io::{Cursor, Read, Seek, SeekFrom};
 
 // Helper bits
 
-// Value reader macro, handles error translation as well
+// Value reader macro, handles error translation as well. Errors keep the position the read
+// started at, which is relative to the start of whatever the reader is over. Readers are cursors
+// over entry buffers or an Input, none of which seek to tell the position
 macro_rules! read_value {
+    ($reader:ident, $value:ident, read_u8) => {
+        let position = std::io::Seek::stream_position($reader.by_ref()).ok();
+        let $value = $reader.read_u8().map_err(|e| {
+            crate::error::Error::into_value_read_error(e.into(), stringify!($value), "read_u8")
+                .at_position(position)
+        })?;
+    };
     ($reader:ident, $value:ident, $type:ident) => {
+        let position = std::io::Seek::stream_position($reader.by_ref()).ok();
         let $value = $reader.$type::<byteorder::LittleEndian>().map_err(|e| {
             crate::error::Error::into_value_read_error(
                 e.into(),
                 stringify!($value),
                 stringify!($type),
             )
+            .at_position(position)
         })?;
     };
 }
@@ -62,71 +75,101 @@ pub struct Entry {
 
 impl Entry {
     pub fn get_entry_bytes<R: Read + Seek>(reader: &mut R, offset: u64) -> crate::Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        Self::read_entry_bytes(reader, offset, &mut buffer)?;
+        Ok(buffer)
+    }
+
+    // Same as get_entry_bytes but reuses the buffer, fixups are applied in place
+    pub fn read_entry_bytes<R: Read + Seek>(
+        reader: &mut R,
+        offset: u64,
+        buffer: &mut Vec<u8>,
+    ) -> crate::Result<()> {
         trace!("Reading entry bytes from offset {}", offset);
         // Ensure we're at the right offset
         reader.seek(SeekFrom::Start(offset))?;
         // Read first 48 bytes
-        let mut buffer: Vec<u8> = Vec::new();
+        buffer.clear();
         reader
             .take(48)
-            .read_to_end(&mut buffer)
+            .read_to_end(buffer)
             .map_err(|e| Error::into_buffer_fill_error(e.into(), offset, 48))?;
         // Generate header from first 48 bytes
-        let mut header_reader = Cursor::new(&buffer);
-        let header = Header::from_reader(&mut header_reader)?;
-        // End early if header is zeroed
-        if header.is_zeroed() {
-            // If zeroed assume default size
-            trace!(
-                "Found zeroed entry, returning zeroed record of size {}",
-                crate::MFT_RECORD_SIZE
-            );
-            reader
-                .take(crate::MFT_RECORD_SIZE - 48)
-                .read_to_end(&mut buffer)
-                .map_err(|e| {
-                    Error::into_buffer_fill_error(
-                        e.into(),
-                        offset + 48,
-                        crate::MFT_RECORD_SIZE - 48,
-                    )
-                })?;
-            return Ok(buffer);
-        }
+        let mut header_reader = Cursor::new(buffer.as_slice());
+        let header = Header::from_reader(&mut header_reader).map_err(|e| e.relative_to(offset))?;
+        // Zeroed and garbage headers can't be trusted for sizes or fixups, assume default size
+        let status = header.status();
+        let record_size = header.record_size();
         trace!(
-            "Reading {} bytes from offset {}",
-            header.total_entry_size - 48,
-            offset + 48
+            "Reading {} bytes from offset {} ({:?} record)",
+            record_size - 48,
+            offset + 48,
+            status
         );
         // Read the rest of the data
         reader
-            .take(header.total_entry_size as u64 - 48)
-            .read_to_end(&mut buffer)
-            .map_err(|e| {
-                Error::into_buffer_fill_error(
-                    e.into(),
-                    offset + 48,
-                    header.total_entry_size as u64 - 48,
-                )
-            })?;
+            .take(record_size - 48)
+            .read_to_end(buffer)
+            .map_err(|e| Error::into_buffer_fill_error(e.into(), offset + 48, record_size - 48))?;
+        Self::fix_entry_bytes(buffer, &header, offset);
+        Ok(())
+    }
 
+    // Same as read_entry_bytes for an input that is already in memory, e.g. a mapped file
+    pub fn copy_entry_bytes(input: &[u8], offset: u64, buffer: &mut Vec<u8>) -> crate::Result<()> {
+        trace!("Copying entry bytes from offset {}", offset);
+        let rest = usize::try_from(offset)
+            .ok()
+            .and_then(|offset| input.get(offset..))
+            .unwrap_or_default();
+        let header = Header::from_reader(&mut Cursor::new(&rest[..rest.len().min(48)]))
+            .map_err(|e| e.relative_to(offset))?;
+        // Like reading through a file, a record cut off by the end of the input is left short
+        let record_size = usize::try_from(header.record_size()).unwrap_or(usize::MAX);
+        buffer.clear();
+        buffer.extend_from_slice(&rest[..rest.len().min(record_size)]);
+        Self::fix_entry_bytes(buffer, &header, offset);
+        Ok(())
+    }
+
+    fn fix_entry_bytes(buffer: &mut [u8], header: &Header, offset: u64) {
+        if !header.is_plausible() {
+            return;
+        }
         trace!("Applying fixup values to entry bytes");
-        // Get and apply fixup
-        let fix_up: Vec<u8> = buffer[header.offset_to_fixup as usize
-            ..(header.offset_to_fixup + header.num_of_fixup * 2 + 2) as usize]
-            .to_vec();
+        if !Self::apply_fixups(buffer, header) {
+            trace!(
+                "Fixup values do not match sector tails for entry at {}",
+                offset
+            );
+        }
+    }
+
+    // Replaces the last 2 bytes of each 512 byte sector with the values from the fixup array,
+    // returns false if any sector tail did not hold the update sequence number (torn write)
+    pub fn apply_fixups(buffer: &mut [u8], header: &Header) -> bool {
+        let fixup_start = header.offset_to_fixup as usize;
+        let fixup_end = fixup_start + header.num_of_fixup as usize * 2;
+        let fix_up: Vec<u8> = match buffer.get(fixup_start..fixup_end) {
+            Some(fix_up) if fix_up.len() >= 2 => fix_up.to_vec(),
+            _ => return false,
+        };
+        let mut is_valid = true;
         for i in 1..(fix_up.len() / 2) {
             // Replace last 2 bytes of each 512 sector
             let replace_offset = i * 512 - 2;
             let fix_up_offset = i * 2;
-            if replace_offset > buffer.len() {
+            if replace_offset + 2 > buffer.len() {
                 break;
             }
+            if buffer[replace_offset..replace_offset + 2] != fix_up[0..2] {
+                is_valid = false;
+            }
             buffer[replace_offset] = fix_up[fix_up_offset];
             buffer[replace_offset + 1] = fix_up[fix_up_offset + 1];
         }
-        // Return full entry buffer
-        Ok(buffer)
+        is_valid
     }
 
     pub fn from_reader<R: Read + Seek>(
@@ -134,23 +177,35 @@ impl Entry {
         prev_entry: Option<Self>,
     ) -> crate::Result<Self> {
         let (file_offset, entry_n) = match &prev_entry {
-            Some(prev) => (
-                prev.offset
-                    + if prev.header.total_entry_size == 0 {
-                        1024
-                    } else {
-                        prev.header.total_entry_size as u64
-                    },
-                prev.entry_n + 1,
-            ),
+            Some(prev) => (prev.offset + prev.header.record_size(), prev.entry_n + 1),
             None => (0, 0),
         };
+        Self::from_reader_at(reader, file_offset, entry_n)
+    }
+
+    // Parse an entry at an arbitrary offset, used when the entry is not part of a contiguous MFT
+    pub fn from_reader_at<R: Read + Seek>(
+        reader: &mut R,
+        file_offset: u64,
+        entry_n: u64,
+    ) -> crate::Result<Self> {
         // Get entry bytes
-        let entry_bytes = Self::get_entry_bytes(reader, file_offset)?;
-        let mut header_reader = Cursor::new(&entry_bytes[0..48]);
-        let header = Header::from_reader(&mut header_reader)?;
-        if header.is_zeroed() {
-            reader.seek(SeekFrom::Start(file_offset + crate::MFT_RECORD_SIZE))?;
+        let entry_bytes =
+            Self::get_entry_bytes(reader, file_offset).map_err(|e| e.for_entry(entry_n))?;
+        Self::from_entry_bytes(&entry_bytes, file_offset, entry_n)
+    }
+
+    // Parse an entry from a buffer that already has fixups applied
+    pub fn from_entry_bytes(
+        entry_bytes: &[u8],
+        file_offset: u64,
+        entry_n: u64,
+    ) -> crate::Result<Self> {
+        let mut header_reader = Cursor::new(entry_bytes);
+        let header = Header::from_reader(&mut header_reader)
+            .map_err(|e| e.in_entry(entry_n, file_offset))?;
+        let status = header.status();
+        if status == RecordStatus::Zeroed || status == RecordStatus::Garbage {
             return Ok(Self {
                 offset: file_offset,
                 entry_n,
@@ -161,16 +216,26 @@ impl Entry {
 
         // Get attributes
         let mut attributes: Vec<Attribute> = Vec::new();
-        let mut cursor = Cursor::new(&entry_bytes);
         let mut offset = header.attrs_offset as u64;
-        cursor.seek(SeekFrom::Start(offset))?;
         // Iterate over buffer to get all attributes
-        while let Some(attribute) = Attribute::from_buffer(&entry_bytes, offset)? {
-            if !Attribute::is_valid_type_code(attribute.type_code) {
+        loop {
+            let attribute = match Attribute::from_buffer(entry_bytes, offset) {
+                Ok(Some(attribute)) => attribute,
+                Ok(None) => break,
+                // BAAD records are known to be incomplete, keep whatever could be read
+                Err(e) if status == RecordStatus::Baad => {
+                    debug!(
+                        "Stopped reading attributes of BAAD entry {}: {}",
+                        entry_n, e
+                    );
+                    break;
+                }
+                Err(e) => return Err(e.in_entry(entry_n, file_offset)),
+            };
+            if !Attribute::is_valid_type_code(attribute.type_code) || attribute.record_len == 0 {
                 break;
             }
             offset += attribute.record_len as u64;
-            cursor.seek(SeekFrom::Start(offset))?;
             attributes.push(attribute);
         }
 
@@ -184,6 +249,178 @@ impl Entry {
             attributes,
         })
     }
+
+    // Opt-in scan of the bytes between used_entry_size and total_entry_size for leftover attributes
+    pub fn scan_slack<R: Read + Seek>(&self, reader: &mut R) -> crate::Result<Vec<SlackAttribute>> {
+        if self.header.is_zeroed() {
+            return Ok(Vec::new());
+        }
+        let entry_bytes = Self::get_entry_bytes(reader, self.offset)?;
+        Ok(Self::scan_slack_bytes(
+            &entry_bytes,
+            &self.header,
+            self.offset,
+        ))
+    }
+
+    pub fn scan_slack_bytes(
+        entry_bytes: &[u8],
+        header: &Header,
+        file_offset: u64,
+    ) -> Vec<SlackAttribute> {
+        trace!("Scanning slack of entry at offset {}", file_offset);
+        let end = (header.total_entry_size as usize).min(entry_bytes.len());
+        let buffer = &entry_bytes[..end];
+        // Attributes are always 8 byte aligned within a record
+        let mut offset = (header.used_entry_size as usize + 7) & !7;
+        let mut found = Vec::new();
+        while offset + 24 <= end {
+            match SlackAttribute::from_buffer(buffer, offset, file_offset) {
+                Some(attribute) => {
+                    offset += attribute.record_len as usize;
+                    found.push(attribute);
+                }
+                None => offset += 8,
+            }
+        }
+        found
+    }
+}
+
+// Attribute recovered from record slack, these are remnants of an earlier version of the record
+#[derive(Debug, Clone, Serialize)]
+pub struct SlackAttribute {
+    pub offset: u64, // Absolute offset of the attribute header in the MFT
+    pub type_code: u32,
+    pub record_len: u32,
+    pub instance: u16,
+    pub content: SlackContent,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type")]
+pub enum SlackContent {
+    FileName {
+        name: String,
+        name_space: u8,
+        parent_entry: u64,
+        parent_sequence: u16,
+        created: DateTime<Utc>,
+        modified: DateTime<Utc>,
+    },
+    Data {
+        name: Option<String>,
+        size: u64,
+        resident: Option<crate::attributes::Data>, // Only resident data survives in slack
+    },
+}
+
+impl SlackAttribute {
+    // Slack is mostly garbage so every header field is sanity checked before attempting a parse
+    fn from_buffer(buffer: &[u8], offset: usize, file_offset: u64) -> Option<Self> {
+        let type_code = LittleEndian::read_u32(buffer.get(offset..offset + 4)?);
+        let record_len = LittleEndian::read_u32(buffer.get(offset + 4..offset + 8)?);
+        let form_code = *buffer.get(offset + 8)?;
+        if !(type_code == 0x30 || type_code == 0x80)
+            || form_code > 1
+            || record_len < 24
+            || record_len % 8 != 0
+            || offset + record_len as usize > buffer.len()
+        {
+            return None;
+        }
+        let record = &buffer[..offset + record_len as usize];
+        let attribute = Attribute::from_buffer(record, offset as u64).ok()??;
+        let content = match attribute.data {
+            AttributeData::Resident {
+                data_size,
+                data_offset,
+                ..
+            } => {
+                let start = offset + data_offset as usize;
+                let data = record.get(start..start + data_size as usize)?;
+                if type_code == 0x30 {
+                    let file_name =
+                        crate::attributes::FileName::from_reader(&mut Cursor::new(data)).ok()?;
+                    if file_name.name_length == 0 || file_name.name_space > 3 {
+                        return None;
+                    }
+                    SlackContent::FileName {
+                        name: file_name.name,
+                        name_space: file_name.name_space,
+                        parent_entry: file_name.parent_file_reference.entry,
+                        parent_sequence: file_name.parent_file_reference.sequence,
+                        created: file_name.creation_time,
+                        modified: file_name.modification_time,
+                    }
+                } else {
+                    let is_zone_identifier = attribute.name.as_deref() == Some("Zone.Identifier");
+                    SlackContent::Data {
+                        resident: crate::attributes::Data::from_buffer(data, is_zone_identifier)
+                            .ok(),
+                        name: attribute.name,
+                        size: data_size as u64,
+                    }
+                }
+            }
+            // $FILE_NAME is always resident, a non-resident one is a false positive
+            AttributeData::NonResident { data_size, .. } if type_code == 0x80 => {
+                SlackContent::Data {
+                    name: attribute.name,
+                    size: data_size,
+                    resident: None,
+                }
+            }
+            AttributeData::NonResident { .. } => return None,
+        };
+        Some(Self {
+            offset: file_offset + offset as u64,
+            type_code,
+            record_len,
+            instance: attribute.instance,
+            content,
+        })
+    }
+}
+
+impl fmt::Display for SlackAttribute {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.content {
+            SlackContent::FileName {
+                name, parent_entry, ..
+            } => write!(
+                f,
+                "slack@{:#x} $FILE_NAME {} (parent {})",
+                self.offset, name, parent_entry
+            ),
+            SlackContent::Data { name, size, .. } => write!(
+                f,
+                "slack@{:#x} $DATA:{} ({} bytes)",
+                self.offset,
+                name.as_deref().unwrap_or_default(),
+                size
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum RecordStatus {
+    Valid,   // FILE signature with a consistent header
+    Baad,    // BAAD signature, NTFS found a multi-sector transfer error
+    Zeroed,  // Never used or wiped
+    Garbage, // Anything else, including FILE records with inconsistent headers
+}
+
+impl fmt::Display for RecordStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecordStatus::Valid => write!(f, "valid"),
+            RecordStatus::Baad => write!(f, "baad"),
+            RecordStatus::Zeroed => write!(f, "zeroed"),
+            RecordStatus::Garbage => write!(f, "garbage"),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -202,6 +439,7 @@ pub struct Header {
     pub total_entry_size: u32,
     pub base_mft_record: FileReference,
     pub next_attr_id: u16,
+    pub record_number: u32, // Only present from NTFS 3.1, see Header::entry_number
 }
 
 impl Header {
@@ -219,13 +457,55 @@ impl Header {
             && self.base_mft_record == 0
             && self.next_attr_id == 0
     }
+    // Before NTFS 3.1 the fixup array started at 0x2A, overlapping the record number
+    pub fn entry_number(&self) -> Option<u64> {
+        match self.offset_to_fixup >= 0x30 {
+            true => Some(self.record_number as u64),
+            false => None,
+        }
+    }
+
+    pub fn status(&self) -> RecordStatus {
+        if self.is_zeroed() {
+            RecordStatus::Zeroed
+        } else if &self.sig == b"BAAD" {
+            RecordStatus::Baad
+        } else if &self.sig == b"FILE" && self.is_plausible() {
+            RecordStatus::Valid
+        } else {
+            RecordStatus::Garbage
+        }
+    }
+
+    // Size of the record on disk, falls back to the default when the header can't be trusted
+    pub fn record_size(&self) -> u64 {
+        match self.is_plausible() {
+            true => self.total_entry_size as u64,
+            false => crate::MFT_RECORD_SIZE,
+        }
+    }
+
+    // Structural checks, also used for headers found outside of a known MFT (carving)
+    pub fn is_plausible(&self) -> bool {
+        let fixup_end = self.offset_to_fixup as u32 + self.num_of_fixup as u32 * 2;
+        (&self.sig == b"FILE" || &self.sig == b"BAAD")
+            && (self.total_entry_size == 1024 || self.total_entry_size == 4096)
+            && self.num_of_fixup as u32 == self.total_entry_size / 512 + 1
+            && self.offset_to_fixup >= 0x2A
+            && fixup_end <= self.attrs_offset as u32
+            && self.attrs_offset as u32 + 8 <= self.used_entry_size
+            && self.used_entry_size <= self.total_entry_size
+    }
+
     pub fn from_reader<R: Read + Seek>(reader: &mut R) -> crate::Result<Self> {
         // let mut reader = BufReader::new(buffer);
         //
         let mut sig_buffer: [u8; 4] = [0; 4];
-        reader
-            .read_exact(&mut sig_buffer)
-            .map_err(|e| Error::into_value_read_error(e.into(), "sig_buffer", "read_u8 * 4"))?;
+        let position = reader.stream_position().ok();
+        reader.read_exact(&mut sig_buffer).map_err(|e| {
+            Error::into_value_read_error(e.into(), "sig_buffer", "read_u8 * 4")
+                .at_position(position)
+        })?;
         //
         read_value!(reader, offset_to_fixup, read_u16);
         read_value!(reader, num_of_fixup, read_u16);
@@ -239,6 +519,8 @@ impl Header {
         read_value!(reader, record, read_u64);
         let base_mft_record = FileReference::from(record);
         read_value!(reader, next_attr_id, read_u16);
+        read_value!(reader, _padding, read_u16);
+        read_value!(reader, record_number, read_u32);
         // Consolidate errors
         Ok(Header {
             sig: sig_buffer,
@@ -253,6 +535,7 @@ impl Header {
             total_entry_size,
             base_mft_record,
             next_attr_id,
+            record_number,
         })
     }
 }
@@ -304,13 +587,16 @@ impl Attribute {
         if !Self::is_valid_type_code(type_code) {
             return Ok(None);
         }
+        Self::from_reader(&mut reader, offset, type_code)
+            .map(Some)
+            .map_err(|e| e.in_attribute(type_code))
+    }
+
+    // Rest of the attribute header, the reader is positioned just after the type code
+    fn from_reader(reader: &mut Cursor<&[u8]>, offset: u64, type_code: u32) -> crate::Result<Self> {
         read_value!(reader, record_len, read_u32);
-        let form_code = reader
-            .read_u8()
-            .map_err(|e| Error::into_value_read_error(e.into(), "form_code", "read_u8"))?;
-        let name_len = reader
-            .read_u8()
-            .map_err(|e| Error::into_value_read_error(e.into(), "name_len", "read_u8"))?;
+        read_value!(reader, form_code, read_u8);
+        read_value!(reader, name_len, read_u8);
         read_value!(reader, name_offset, read_u16);
         read_value!(reader, flags, read_u16);
         read_value!(reader, instance, read_u16);
@@ -318,12 +604,8 @@ impl Attribute {
             0x00 => {
                 read_value!(reader, data_size, read_u32);
                 read_value!(reader, data_offset, read_u16);
-                let indexed_flag = reader.read_u8().map_err(|e| {
-                    Error::into_value_read_error(e.into(), "indexed_flag", "read_u8")
-                })?;
-                let _padding = reader
-                    .read_u8()
-                    .map_err(|e| Error::into_value_read_error(e.into(), "_padding", "read_u8"))?;
+                read_value!(reader, indexed_flag, read_u8);
+                read_value!(reader, _padding, read_u8);
                 AttributeData::Resident {
                     data_size,
                     data_offset,
@@ -359,7 +641,14 @@ impl Attribute {
                     compressed_size,
                 }
             }
-            _ => unreachable!(),
+            _ => {
+                return Err(Error::invalid_value(
+                    "form_code",
+                    "read_u8",
+                    format!("Unknown attribute form code {}", form_code),
+                )
+                .at_position(Some(offset + 8)))
+            }
         };
         // Get name
         reader.seek(SeekFrom::Start(offset + name_offset as u64))?;
@@ -367,17 +656,15 @@ impl Attribute {
         let name = if name_len > 0 {
             let mut s = String::new();
             for _ in 0..name_len {
-                let c = reader
-                    .read_u16::<LittleEndian>()
-                    .map_err(|e| Error::into_value_read_error(e.into(), "name_char", "read_u16"))?;
-                s.push(c as u8 as char);
+                read_value!(reader, name_char, read_u16);
+                s.push(name_char as u8 as char);
             }
             Some(s)
         } else {
             None
         };
         //
-        Ok(Some(Attribute {
+        Ok(Attribute {
             offset,
             type_code,
             record_len,
@@ -388,7 +675,7 @@ impl Attribute {
             flags,
             instance,
             data,
-        }))
+        })
     }
 
     pub fn is_valid_type_code(type_code: u32) -> bool {
@@ -399,90 +686,109 @@ impl Attribute {
         ];
         VALID_CODES.contains(&type_code)
     }
+
+    pub fn type_name(type_code: u32) -> &'static str {
+        match type_code {
+            0x10 => "$STANDARD_INFORMATION",
+            0x20 => "$ATTRIBUTE_LIST",
+            0x30 => "$FILE_NAME",
+            0x40 => "$OBJECT_ID",
+            0x50 => "$SECURITY_DESCRIPTOR",
+            0x60 => "$VOLUME_NAME",
+            0x70 => "$VOLUME_INFORMATION",
+            0x80 => "$DATA",
+            0x90 => "$INDEX_ROOT",
+            0xa0 => "$INDEX_ALLOCATION",
+            0xb0 => "$BITMAP",
+            0xc0 => "$REPARSE_POINT",
+            0xd0 => "$EA_INFORMATION",
+            0xe0 => "$EA",
+            0xf0 => "$PROPERTY_SET",
+            0x100 => "$LOGGED_UTILITY_STREAM",
+            _ => "UNKNOWN",
+        }
+    }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
 
+    pub(crate) const TEST_ENTRY: [u8; 1024] = [
+        0x46, 0x49, 0x4c, 0x45, 0x30, 0x00, 0x03, 0x00, 0x4a, 0xcc, 0x37, 0x0c, 0x00, 0x00, 0x00,
+        0x00, 0x01, 0x00, 0x0a, 0x00, 0x38, 0x00, 0x01, 0x00, 0xa0, 0x02, 0x00, 0x00, 0x00, 0x04,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x11, 0x00, 0x00, 0x00, 0xa9,
+        0xa2, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00,
+        0x60, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x48, 0x00, 0x00,
+        0x00, 0x18, 0x00, 0x00, 0x00, 0x50, 0xaa, 0xbc, 0xa8, 0x0d, 0xad, 0xd5, 0x01, 0x50, 0xaa,
+        0xbc, 0xa8, 0x0d, 0xad, 0xd5, 0x01, 0x32, 0x5f, 0xbf, 0x58, 0x44, 0xcc, 0xd8, 0x01, 0x32,
+        0x5f, 0xbf, 0x58, 0x44, 0xcc, 0xd8, 0x01, 0x20, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xc4, 0x04, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x6c, 0x1d, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x48, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x0c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0xc0, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xc0, 0x01,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x31, 0x01, 0x8e, 0x8d, 0x0a, 0x00, 0x00, 0x00, 0x30,
+        0x00, 0x00, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0d, 0x00,
+        0x66, 0x00, 0x00, 0x00, 0x18, 0x00, 0x01, 0x00, 0x0e, 0x01, 0x00, 0x00, 0x00, 0x00, 0x01,
+        0x00, 0x50, 0xaa, 0xbc, 0xa8, 0x0d, 0xad, 0xd5, 0x01, 0x50, 0xaa, 0xbc, 0xa8, 0x0d, 0xad,
+        0xd5, 0x01, 0xd7, 0x7c, 0xee, 0x5d, 0x4c, 0xcc, 0xd8, 0x01, 0x6c, 0xdc, 0xfc, 0x79, 0x04,
+        0xbe, 0xd6, 0x01, 0x00, 0x30, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xb8, 0x21, 0x01, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x12, 0x00, 0x63,
+        0x00, 0x6c, 0x00, 0x72, 0x00, 0x63, 0x00, 0x6f, 0x00, 0x6d, 0x00, 0x70, 0x00, 0x72, 0x00,
+        0x65, 0x00, 0x73, 0x00, 0x73, 0x00, 0x69, 0x00, 0x6f, 0x00, 0x6e, 0x00, 0x2e, 0x00, 0x64,
+        0x00, 0x6c, 0x00, 0x6c, 0x00, 0x00, 0x00, 0x30, 0x00, 0x00, 0x00, 0x80, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0b, 0x00, 0x66, 0x00, 0x00, 0x00, 0x18, 0x00, 0x01,
+        0x00, 0x1d, 0x01, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x50, 0xaa, 0xbc, 0xa8, 0x0d, 0xad,
+        0xd5, 0x01, 0x50, 0xaa, 0xbc, 0xa8, 0x0d, 0xad, 0xd5, 0x01, 0xd7, 0x7c, 0xee, 0x5d, 0x4c,
+        0xcc, 0xd8, 0x01, 0x6c, 0xdc, 0xfc, 0x79, 0x04, 0xbe, 0xd6, 0x01, 0x00, 0x30, 0x01, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0xb8, 0x21, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x12, 0x00, 0x63, 0x00, 0x6c, 0x00, 0x72, 0x00, 0x63, 0x00,
+        0x6f, 0x00, 0x6d, 0x00, 0x70, 0x00, 0x72, 0x00, 0x65, 0x00, 0x73, 0x00, 0x73, 0x00, 0x69,
+        0x00, 0x6f, 0x00, 0x6e, 0x00, 0x2e, 0x00, 0x64, 0x00, 0x6c, 0x00, 0x6c, 0x00, 0x00, 0x00,
+        0xd0, 0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0f,
+        0x00, 0x08, 0x00, 0x00, 0x00, 0x18, 0x00, 0x00, 0x00, 0x6f, 0x00, 0x00, 0x00, 0x7c, 0x00,
+        0x05, 0x00, 0xe0, 0x00, 0x00, 0x00, 0x98, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x10, 0x00, 0x7c, 0x00, 0x00, 0x00, 0x18, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00,
+        0x00, 0x16, 0x1e, 0x00, 0x24, 0x4b, 0x45, 0x52, 0x4e, 0x45, 0x4c, 0x2e, 0x50, 0x55, 0x52,
+        0x47, 0x45, 0x2e, 0x45, 0x53, 0x42, 0x43, 0x41, 0x43, 0x48, 0x45, 0x00, 0x1e, 0x00, 0x00,
+        0x00, 0x03, 0x00, 0x02, 0x06, 0x78, 0x4c, 0x24, 0x47, 0x44, 0xcc, 0xd8, 0x01, 0x80, 0x66,
+        0x42, 0xa5, 0x70, 0x73, 0xd3, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x3c, 0x00, 0x00, 0x00, 0x00, 0x19, 0x18, 0x00, 0x24, 0x4b, 0x45, 0x52, 0x4e, 0x45, 0x4c,
+        0x2e, 0x50, 0x55, 0x52, 0x47, 0x45, 0x2e, 0x41, 0x50, 0x50, 0x58, 0x46, 0x49, 0x43, 0x41,
+        0x43, 0x48, 0x45, 0x00, 0x78, 0x4c, 0x24, 0x47, 0x44, 0xcc, 0xd8, 0x01, 0x08, 0x6c, 0x1d,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x4c, 0xcc, 0xd8, 0x01, 0xff, 0xff, 0xff, 0xff, 0x82, 0x79, 0x47, 0x11, 0x00, 0x30, 0x01,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0xb8, 0x21, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x12, 0x00, 0x63, 0x00, 0x6c, 0x00, 0x72, 0x00, 0x63,
+        0x00, 0x6f, 0x00, 0x6d, 0x00, 0x70, 0x00, 0x72, 0x00, 0x65, 0x00, 0x73, 0x00, 0x73, 0x00,
+        0x69, 0x00, 0x6f, 0x00, 0x6e, 0x00, 0x2e, 0x00, 0x64, 0x00, 0x6c, 0x00, 0x6c, 0x00, 0x00,
+        0x00, 0x30, 0x00, 0x00, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x0e, 0x00, 0x66, 0x00, 0x00, 0x00, 0x18, 0x00, 0x01, 0x00, 0xd5, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x01, 0x00, 0x50, 0xaa, 0xbc, 0xa8, 0x0d, 0xad, 0xd5, 0x01, 0x50, 0xaa, 0xbc, 0xa8,
+        0x0d, 0xad, 0xd5, 0x01, 0xd7, 0x7c, 0xee, 0x5d, 0x4c, 0xcc, 0xd8, 0x01, 0x6c, 0xdc, 0xfc,
+        0x79, 0x04, 0xbe, 0xd6, 0x01, 0x00, 0x30, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xb8, 0x21,
+        0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x12,
+        0x00, 0x63, 0x00, 0x6c, 0x00, 0x72, 0x00, 0x63, 0x00, 0x6f, 0x00, 0x6d, 0x00, 0x70, 0x00,
+        0x72, 0x00, 0x65, 0x00, 0x73, 0x00, 0x73, 0x00, 0x69, 0x00, 0x6f, 0x00, 0x6e, 0x00, 0x2e,
+        0x00, 0x64, 0x00, 0x6c, 0x00, 0x6c, 0x00, 0x00, 0x00, 0x30, 0x00, 0x00, 0x00, 0x80, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0a, 0x00, 0x66, 0x00, 0x00, 0x00, 0x18,
+        0x00, 0x01, 0x00, 0xd5, 0x01, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x50, 0xaa, 0xbc, 0xa8,
+        0x0d, 0xad, 0xd5, 0x01, 0x50, 0xaa, 0xbc, 0xa8, 0x0d, 0xad, 0xd5, 0x01, 0xd7, 0x7c, 0xee,
+        0x5d, 0x4c, 0xcc, 0xd8, 0x01, 0x6c, 0xdc, 0xfc, 0x79, 0x04, 0xbe, 0xd6, 0x01, 0x00, 0x30,
+        0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xb8, 0x21, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x12, 0x00, 0x63, 0x00, 0x6c, 0x00, 0x72, 0x00,
+        0x63, 0x00, 0x6f, 0x00, 0x6d, 0x00, 0x70, 0x00, 0x72, 0x00, 0x65, 0x00, 0x73, 0x00, 0x73,
+        0x00, 0x69, 0x00, 0x6f, 0x00, 0x6e, 0x00, 0x2e, 0x00, 0x64, 0x00, 0x6c, 0x00, 0x6c, 0x00,
+        0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0x82, 0x79, 0x47, 0x11, 0x64, 0x00, 0x6c, 0x00, 0x6c,
+        0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0x82, 0x79, 0x47, 0x11, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x05, 0x00,
+    ];
+
     #[test]
     fn test_entry_parse() {
-        let data = vec![
-            0x46, 0x49, 0x4c, 0x45, 0x30, 0x00, 0x03, 0x00, 0x4a, 0xcc, 0x37, 0x0c, 0x00, 0x00,
-            0x00, 0x00, 0x01, 0x00, 0x0a, 0x00, 0x38, 0x00, 0x01, 0x00, 0xa0, 0x02, 0x00, 0x00,
-            0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x11, 0x00,
-            0x00, 0x00, 0xa9, 0xa2, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-            0x10, 0x00, 0x00, 0x00, 0x60, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-            0x00, 0x00, 0x48, 0x00, 0x00, 0x00, 0x18, 0x00, 0x00, 0x00, 0x50, 0xaa, 0xbc, 0xa8,
-            0x0d, 0xad, 0xd5, 0x01, 0x50, 0xaa, 0xbc, 0xa8, 0x0d, 0xad, 0xd5, 0x01, 0x32, 0x5f,
-            0xbf, 0x58, 0x44, 0xcc, 0xd8, 0x01, 0x32, 0x5f, 0xbf, 0x58, 0x44, 0xcc, 0xd8, 0x01,
-            0x20, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xc4, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-            0x00, 0x00, 0x00, 0x00, 0x08, 0x6c, 0x1d, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0x00,
-            0x00, 0x00, 0x48, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x00,
-            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-            0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00,
-            0x00, 0x00, 0x00, 0x00, 0xc0, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xc0, 0x01,
-            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x31, 0x01, 0x8e, 0x8d, 0x0a, 0x00, 0x00, 0x00,
-            0x30, 0x00, 0x00, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-            0x0d, 0x00, 0x66, 0x00, 0x00, 0x00, 0x18, 0x00, 0x01, 0x00, 0x0e, 0x01, 0x00, 0x00,
-            0x00, 0x00, 0x01, 0x00, 0x50, 0xaa, 0xbc, 0xa8, 0x0d, 0xad, 0xd5, 0x01, 0x50, 0xaa,
-            0xbc, 0xa8, 0x0d, 0xad, 0xd5, 0x01, 0xd7, 0x7c, 0xee, 0x5d, 0x4c, 0xcc, 0xd8, 0x01,
-            0x6c, 0xdc, 0xfc, 0x79, 0x04, 0xbe, 0xd6, 0x01, 0x00, 0x30, 0x01, 0x00, 0x00, 0x00,
-            0x00, 0x00, 0xb8, 0x21, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00,
-            0x00, 0x00, 0x00, 0x00, 0x12, 0x00, 0x63, 0x00, 0x6c, 0x00, 0x72, 0x00, 0x63, 0x00,
-            0x6f, 0x00, 0x6d, 0x00, 0x70, 0x00, 0x72, 0x00, 0x65, 0x00, 0x73, 0x00, 0x73, 0x00,
-            0x69, 0x00, 0x6f, 0x00, 0x6e, 0x00, 0x2e, 0x00, 0x64, 0x00, 0x6c, 0x00, 0x6c, 0x00,
-            0x00, 0x00, 0x30, 0x00, 0x00, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-            0x00, 0x00, 0x0b, 0x00, 0x66, 0x00, 0x00, 0x00, 0x18, 0x00, 0x01, 0x00, 0x1d, 0x01,
-            0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x50, 0xaa, 0xbc, 0xa8, 0x0d, 0xad, 0xd5, 0x01,
-            0x50, 0xaa, 0xbc, 0xa8, 0x0d, 0xad, 0xd5, 0x01, 0xd7, 0x7c, 0xee, 0x5d, 0x4c, 0xcc,
-            0xd8, 0x01, 0x6c, 0xdc, 0xfc, 0x79, 0x04, 0xbe, 0xd6, 0x01, 0x00, 0x30, 0x01, 0x00,
-            0x00, 0x00, 0x00, 0x00, 0xb8, 0x21, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0x00,
-            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x12, 0x00, 0x63, 0x00, 0x6c, 0x00, 0x72, 0x00,
-            0x63, 0x00, 0x6f, 0x00, 0x6d, 0x00, 0x70, 0x00, 0x72, 0x00, 0x65, 0x00, 0x73, 0x00,
-            0x73, 0x00, 0x69, 0x00, 0x6f, 0x00, 0x6e, 0x00, 0x2e, 0x00, 0x64, 0x00, 0x6c, 0x00,
-            0x6c, 0x00, 0x00, 0x00, 0xd0, 0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00,
-            0x00, 0x00, 0x00, 0x00, 0x0f, 0x00, 0x08, 0x00, 0x00, 0x00, 0x18, 0x00, 0x00, 0x00,
-            0x6f, 0x00, 0x00, 0x00, 0x7c, 0x00, 0x05, 0x00, 0xe0, 0x00, 0x00, 0x00, 0x98, 0x00,
-            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x7c, 0x00, 0x00, 0x00,
-            0x18, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x00, 0x16, 0x1e, 0x00, 0x24, 0x4b,
-            0x45, 0x52, 0x4e, 0x45, 0x4c, 0x2e, 0x50, 0x55, 0x52, 0x47, 0x45, 0x2e, 0x45, 0x53,
-            0x42, 0x43, 0x41, 0x43, 0x48, 0x45, 0x00, 0x1e, 0x00, 0x00, 0x00, 0x03, 0x00, 0x02,
-            0x06, 0x78, 0x4c, 0x24, 0x47, 0x44, 0xcc, 0xd8, 0x01, 0x80, 0x66, 0x42, 0xa5, 0x70,
-            0x73, 0xd3, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x00,
-            0x00, 0x00, 0x00, 0x19, 0x18, 0x00, 0x24, 0x4b, 0x45, 0x52, 0x4e, 0x45, 0x4c, 0x2e,
-            0x50, 0x55, 0x52, 0x47, 0x45, 0x2e, 0x41, 0x50, 0x50, 0x58, 0x46, 0x49, 0x43, 0x41,
-            0x43, 0x48, 0x45, 0x00, 0x78, 0x4c, 0x24, 0x47, 0x44, 0xcc, 0xd8, 0x01, 0x08, 0x6c,
-            0x1d, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-            0x00, 0x00, 0x4c, 0xcc, 0xd8, 0x01, 0xff, 0xff, 0xff, 0xff, 0x82, 0x79, 0x47, 0x11,
-            0x00, 0x30, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xb8, 0x21, 0x01, 0x00, 0x00, 0x00,
-            0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x12, 0x00, 0x63, 0x00,
-            0x6c, 0x00, 0x72, 0x00, 0x63, 0x00, 0x6f, 0x00, 0x6d, 0x00, 0x70, 0x00, 0x72, 0x00,
-            0x65, 0x00, 0x73, 0x00, 0x73, 0x00, 0x69, 0x00, 0x6f, 0x00, 0x6e, 0x00, 0x2e, 0x00,
-            0x64, 0x00, 0x6c, 0x00, 0x6c, 0x00, 0x00, 0x00, 0x30, 0x00, 0x00, 0x00, 0x80, 0x00,
-            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0e, 0x00, 0x66, 0x00, 0x00, 0x00,
-            0x18, 0x00, 0x01, 0x00, 0xd5, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x50, 0xaa,
-            0xbc, 0xa8, 0x0d, 0xad, 0xd5, 0x01, 0x50, 0xaa, 0xbc, 0xa8, 0x0d, 0xad, 0xd5, 0x01,
-            0xd7, 0x7c, 0xee, 0x5d, 0x4c, 0xcc, 0xd8, 0x01, 0x6c, 0xdc, 0xfc, 0x79, 0x04, 0xbe,
-            0xd6, 0x01, 0x00, 0x30, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xb8, 0x21, 0x01, 0x00,
-            0x00, 0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x12, 0x00,
-            0x63, 0x00, 0x6c, 0x00, 0x72, 0x00, 0x63, 0x00, 0x6f, 0x00, 0x6d, 0x00, 0x70, 0x00,
-            0x72, 0x00, 0x65, 0x00, 0x73, 0x00, 0x73, 0x00, 0x69, 0x00, 0x6f, 0x00, 0x6e, 0x00,
-            0x2e, 0x00, 0x64, 0x00, 0x6c, 0x00, 0x6c, 0x00, 0x00, 0x00, 0x30, 0x00, 0x00, 0x00,
-            0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0a, 0x00, 0x66, 0x00,
-            0x00, 0x00, 0x18, 0x00, 0x01, 0x00, 0xd5, 0x01, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00,
-            0x50, 0xaa, 0xbc, 0xa8, 0x0d, 0xad, 0xd5, 0x01, 0x50, 0xaa, 0xbc, 0xa8, 0x0d, 0xad,
-            0xd5, 0x01, 0xd7, 0x7c, 0xee, 0x5d, 0x4c, 0xcc, 0xd8, 0x01, 0x6c, 0xdc, 0xfc, 0x79,
-            0x04, 0xbe, 0xd6, 0x01, 0x00, 0x30, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xb8, 0x21,
-            0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-            0x12, 0x00, 0x63, 0x00, 0x6c, 0x00, 0x72, 0x00, 0x63, 0x00, 0x6f, 0x00, 0x6d, 0x00,
-            0x70, 0x00, 0x72, 0x00, 0x65, 0x00, 0x73, 0x00, 0x73, 0x00, 0x69, 0x00, 0x6f, 0x00,
-            0x6e, 0x00, 0x2e, 0x00, 0x64, 0x00, 0x6c, 0x00, 0x6c, 0x00, 0x00, 0x00, 0xff, 0xff,
-            0xff, 0xff, 0x82, 0x79, 0x47, 0x11, 0x64, 0x00, 0x6c, 0x00, 0x6c, 0x00, 0x00, 0x00,
-            0xff, 0xff, 0xff, 0xff, 0x82, 0x79, 0x47, 0x11, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-            0x05, 0x00,
-        ];
+        let data = TEST_ENTRY;
         // Same data as Ox hex
 
         println!("Parsing {} bytes to entry", data.len());
@@ -492,4 +798,60 @@ mod tests {
         println!("Parsed entry: {:#x?}", entry);
         assert!(entry.is_ok());
     }
+
+    #[test]
+    fn test_slack_scan() {
+        let mut reader = Cursor::new(&TEST_ENTRY[..]);
+        let entry = Entry::from_reader(&mut reader, None).unwrap();
+        let slack = entry.scan_slack(&mut reader).unwrap();
+        assert_eq!(slack.len(), 2);
+        assert_eq!(slack[0].offset, 0x2e0);
+        assert_eq!(slack[1].offset, 0x360);
+        for (attribute, expected_parent) in slack.iter().zip([0xd5, 0x1d5]) {
+            assert_eq!(attribute.type_code, 0x30);
+            match &attribute.content {
+                SlackContent::FileName {
+                    name, parent_entry, ..
+                } => {
+                    assert_eq!(name, "clrcompression.dll");
+                    assert_eq!(*parent_entry, expected_parent);
+                }
+                _ => panic!("Expected $FILE_NAME slack attribute"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_slack_scan_ignores_garbage() {
+        let entry_bytes = TEST_ENTRY;
+        let header = Header::from_reader(&mut Cursor::new(&entry_bytes[..])).unwrap();
+        let mut garbage = entry_bytes;
+        for (i, b) in garbage[header.used_entry_size as usize..]
+            .iter_mut()
+            .enumerate()
+        {
+            *b = (i * 31 % 251) as u8;
+        }
+        assert!(Entry::scan_slack_bytes(&garbage, &header, 0).is_empty());
+    }
+
+    #[test]
+    fn copied_entry_matches_read_entry() {
+        // Two records and a short third one cut off by the end of the input
+        let mut input = [TEST_ENTRY, TEST_ENTRY].concat();
+        input.extend_from_slice(&TEST_ENTRY[..600]);
+        for offset in [0, 1024, 2048] {
+            let mut read = Vec::new();
+            Entry::read_entry_bytes(&mut Cursor::new(&input), offset, &mut read).unwrap();
+            let mut copied = Vec::new();
+            Entry::copy_entry_bytes(&input, offset, &mut copied).unwrap();
+            assert_eq!(read, copied);
+        }
+        // Fixups are applied to the copy as well
+        assert_ne!(&input[510..512], &TEST_ENTRY[0x32..0x34]);
+        let mut copied = Vec::new();
+        Entry::copy_entry_bytes(&input, 0, &mut copied).unwrap();
+        assert_eq!(&copied[510..512], &TEST_ENTRY[0x32..0x34]);
+        assert!(Entry::copy_entry_bytes(&input, 4096, &mut copied).is_err());
+    }
 }
@@ -0,0 +1,356 @@
+use chrono::{DateTime, Utc};
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::csv::{Column, CsvWriter};
+use crate::iter::Record;
+use crate::raw::RecordStatus;
+
+// Receives typed records and writes them out in a specific format
+pub trait RecordSink {
+    // Called once before any records
+    fn write_header(&mut self) -> crate::Result<()> {
+        Ok(())
+    }
+
+    fn write_record(&mut self, record: &Record) -> crate::Result<()>;
+
+    // Called once after the last record, flushes the underlying writer
+    fn finish(&mut self) -> crate::Result<()>;
+
+    // Diagnostic records (BAAD, garbage or unparseable entries) are only given to sinks that want them
+    fn has_diagnostic_rows(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputType {
+    // RFC 4180 with a header row, columns are set with ParserSettings::columns
+    #[default]
+    Csv,
+    Tsv,
+    // One JSON object per line
+    Json,
+    // Sleuth Kit bodyfile (3.x) for mactime, one line for SI times and one for FN times
+    Bodyfile,
+    // One row per timestamp event, see crate::timeline
+    TimelineL2tCsv,
+    TimelineJson,
+}
+
+impl OutputType {
+    pub const ALL: [OutputType; 6] = [
+        OutputType::Csv,
+        OutputType::Tsv,
+        OutputType::Json,
+        OutputType::Bodyfile,
+        OutputType::TimelineL2tCsv,
+        OutputType::TimelineJson,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputType::Csv => "csv",
+            OutputType::Tsv => "tsv",
+            OutputType::Json => "json",
+            OutputType::Bodyfile => "bodyfile",
+            OutputType::TimelineL2tCsv => "l2t_csv",
+            OutputType::TimelineJson => "timeline_json",
+        }
+    }
+
+    // Builds the sink for this format, columns are only used by CSV and TSV
+    pub fn sink<'a, W: Write + 'a>(
+        &self,
+        writer: W,
+        columns: &[Column],
+    ) -> Box<dyn RecordSink + 'a> {
+        match self {
+            OutputType::Csv => Box::new(CsvSink::new(writer, CsvWriter::new(columns.to_vec()))),
+            OutputType::Tsv => Box::new(CsvSink::new(writer, CsvWriter::tsv(columns.to_vec()))),
+            OutputType::Json => Box::new(JsonLinesSink::new(writer)),
+            OutputType::Bodyfile => Box::new(BodyfileSink::new(writer)),
+            OutputType::TimelineL2tCsv => Box::new(TimelineSink::l2t_csv(writer)),
+            OutputType::TimelineJson => Box::new(TimelineSink::json(writer)),
+        }
+    }
+
+    // Formats a single record without a trailing newline, empty if the sink skipped it
+    pub fn as_type(&self, record: Record, columns: &[Column]) -> String {
+        let mut buffer = Vec::new();
+        let result = self.sink(&mut buffer, columns).write_record(&record);
+        if let Err(e) = result {
+            warn!("Record {} could not be formatted: {}", record.entry_id, e);
+        }
+        let mut line = String::from_utf8_lossy(&buffer).to_string();
+        if line.ends_with('\n') {
+            line.pop();
+        }
+        line
+    }
+
+    // Header row written before any records, if the format has one
+    pub fn header(&self, columns: &[Column]) -> Option<String> {
+        match self {
+            OutputType::Csv => Some(CsvWriter::new(columns.to_vec()).header()),
+            OutputType::Tsv => Some(CsvWriter::tsv(columns.to_vec()).header()),
+            _ => None,
+        }
+    }
+
+    // Event based formats have no way to represent diagnostic rows
+    pub fn has_diagnostic_rows(&self) -> bool {
+        matches!(self, OutputType::Csv | OutputType::Tsv | OutputType::Json)
+    }
+}
+
+impl fmt::Display for OutputType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for OutputType {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        OutputType::ALL
+            .iter()
+            .find(|o| o.name().eq_ignore_ascii_case(s.trim()))
+            .copied()
+            .ok_or_else(|| crate::Error::InvalidSetting(format!("Unknown output type {}", s)))
+    }
+}
+
+pub struct CsvSink<W: Write> {
+    writer: W,
+    csv: CsvWriter,
+}
+
+impl<W: Write> CsvSink<W> {
+    pub fn new(writer: W, csv: CsvWriter) -> Self {
+        Self { writer, csv }
+    }
+}
+
+impl<W: Write> RecordSink for CsvSink<W> {
+    fn write_header(&mut self) -> crate::Result<()> {
+        writeln!(self.writer, "{}", self.csv.header())?;
+        Ok(())
+    }
+
+    fn write_record(&mut self, record: &Record) -> crate::Result<()> {
+        writeln!(self.writer, "{}", self.csv.row(record))?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> crate::Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+pub struct JsonLinesSink<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> JsonLinesSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+impl<W: Write> RecordSink for JsonLinesSink<W> {
+    fn write_record(&mut self, record: &Record) -> crate::Result<()> {
+        serde_json::to_writer(&mut self.writer, record).map_err(crate::Error::output)?;
+        writeln!(self.writer)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> crate::Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+pub struct BodyfileSink<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> BodyfileSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+// Bodyfile times are unix seconds, with 0 meaning unknown
+fn bodyfile_time(time: Option<DateTime<Utc>>) -> i64 {
+    time.map(|t| t.timestamp().max(0)).unwrap_or_default()
+}
+
+impl<W: Write> RecordSink for BodyfileSink<W> {
+    fn write_record(&mut self, record: &Record) -> crate::Result<()> {
+        if record.status != RecordStatus::Valid || record.error.is_some() {
+            return Ok(());
+        }
+        // MD5|name|inode|mode_as_string|UID|GID|size|atime|mtime|ctime|crtime
+        let inode = format!("{}-128-{}", record.entry_id, record.sequence_number);
+        // The header flag, $STANDARD_INFORMATION attributes of directories are rarely just 0x10
+        let mode = match record.flags & 0x02 != 0 {
+            true => "d/drwxrwxrwx",
+            false => "r/rrwxrwxrwx",
+        };
+        let path = record.path.to_string_lossy();
+        writeln!(
+            self.writer,
+            "0|{}|{}|{}|0|0|{}|{}|{}|{}|{}",
+            path,
+            inode,
+            mode,
+            record.size,
+            bodyfile_time(Some(record.accessed)),
+            bodyfile_time(Some(record.modified)),
+            bodyfile_time(Some(record.changed)),
+            bodyfile_time(Some(record.created)),
+        )?;
+        if record.fn_created.is_some() {
+            writeln!(
+                self.writer,
+                "0|{} ($FILE_NAME)|{}|{}|0|0|{}|{}|{}|{}|{}",
+                path,
+                inode,
+                mode,
+                record.size,
+                bodyfile_time(record.fn_accessed),
+                bodyfile_time(record.fn_modified),
+                bodyfile_time(record.fn_changed),
+                bodyfile_time(record.fn_created),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> crate::Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn has_diagnostic_rows(&self) -> bool {
+        false
+    }
+}
+
+// Writes one line per timeline event, as l2t_csv or JSON
+pub struct TimelineSink<W: Write> {
+    writer: W,
+    json: bool,
+}
+
+impl<W: Write> TimelineSink<W> {
+    pub fn l2t_csv(writer: W) -> Self {
+        Self {
+            writer,
+            json: false,
+        }
+    }
+
+    pub fn json(writer: W) -> Self {
+        Self { writer, json: true }
+    }
+}
+
+impl<W: Write> RecordSink for TimelineSink<W> {
+    fn write_record(&mut self, record: &Record) -> crate::Result<()> {
+        if record.status != RecordStatus::Valid || record.error.is_some() {
+            return Ok(());
+        }
+        for event in crate::timeline::events(record) {
+            if self.json {
+                serde_json::to_writer(&mut self.writer, &event).map_err(crate::Error::output)?;
+                writeln!(self.writer)?;
+            } else {
+                writeln!(self.writer, "{}", event.to_l2t_csv())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> crate::Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn has_diagnostic_rows(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, BASE_TIME};
+    use crate::{Iterator, Parser, ParserSettings};
+
+    #[test]
+    fn sink_test() {
+        let mft = fixtures::volume(&[fixtures::file(16, 5, "a.txt", b"hello", BASE_TIME)]);
+        let path = fixtures::write_temp("sink", &mft);
+        let settings = ParserSettings::new()
+            .drive_char('C')
+            .output_type(OutputType::Json);
+        let parser = Parser::with_settings(&path, settings).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let mut output = Vec::new();
+        let mut iterator = Iterator::from(parser);
+        let mut sink = iterator.inner.settings.output_type.sink(&mut output, &[]);
+        iterator.write_to(sink.as_mut()).unwrap();
+        drop(sink);
+        let lines: Vec<serde_json::Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 13);
+        assert_eq!(lines[12]["path"], "C:/a.txt");
+        assert_eq!(lines[12]["size"], 5);
+    }
+
+    #[test]
+    fn write_failures_are_output_errors() {
+        struct Full;
+        impl Write for Full {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::WriteZero.into())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let mft = fixtures::volume(&[fixtures::file(16, 5, "a.txt", b"hello", BASE_TIME)]);
+        let path = fixtures::write_temp("sink_full", &mft);
+        let parser = Parser::new(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let mut iterator = Iterator::from(parser);
+        for mut sink in [
+            Box::new(JsonLinesSink::new(Full)) as Box<dyn RecordSink>,
+            Box::new(TimelineSink::json(Full)),
+        ] {
+            let record = iterator.next().unwrap().unwrap();
+            let error = sink.write_record(&record).unwrap_err();
+            assert_eq!(error.kind(), crate::ErrorKind::Output);
+        }
+    }
+
+    #[test]
+    fn output_type_names() {
+        for output_type in OutputType::ALL {
+            assert_eq!(
+                output_type.name().parse::<OutputType>().unwrap(),
+                output_type
+            );
+        }
+        assert!("xml".parse::<OutputType>().is_err());
+    }
+}
@@ -0,0 +1,294 @@
+use byteorder::{ByteOrder, LittleEndian};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::raw::{AttributeData, Entry};
+use crate::Parser;
+
+// A contiguous range of clusters in a non-resident stream, lcn is None for sparse runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataRun {
+    pub lcn: Option<u64>,
+    pub clusters: u64,
+}
+
+// Decodes a run list, each run is a header byte with the size of the length and offset fields
+// followed by the length and a signed offset from the previous run's LCN
+pub fn decode_data_runs(buffer: &[u8]) -> crate::Result<Vec<DataRun>> {
+    let invalid = |reason: &str| crate::Error::invalid_value("data_run", "run list", reason.into());
+    let mut runs = Vec::new();
+    let mut offset = 0;
+    let mut lcn: i64 = 0;
+    while let Some(&header) = buffer.get(offset) {
+        if header == 0 {
+            return Ok(runs);
+        }
+        let length_size = (header & 0x0F) as usize;
+        let offset_size = (header >> 4) as usize;
+        if length_size == 0 || length_size > 8 || offset_size > 8 {
+            return Err(invalid("Invalid run header").at_position(Some(offset as u64)));
+        }
+        let fields = buffer
+            .get(offset + 1..offset + 1 + length_size + offset_size)
+            .ok_or_else(|| invalid("Run list is truncated").at_position(Some(offset as u64)))?;
+        let clusters = LittleEndian::read_uint(&fields[..length_size], length_size);
+        let run_lcn = match offset_size {
+            0 => None,
+            _ => {
+                let delta = LittleEndian::read_int(&fields[length_size..], offset_size);
+                lcn = lcn
+                    .checked_add(delta)
+                    .filter(|lcn| *lcn >= 0)
+                    .ok_or_else(|| {
+                        invalid("Run LCN out of range").at_position(Some(offset as u64))
+                    })?;
+                Some(lcn as u64)
+            }
+        };
+        runs.push(DataRun {
+            lcn: run_lcn,
+            clusters,
+        });
+        offset += 1 + length_size + offset_size;
+    }
+    Err(invalid("Run list has no terminator"))
+}
+
+// Where the contents of a $DATA stream are
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stream {
+    Resident(Vec<u8>),
+    // Only readable with the volume the MFT came from, see Volume
+    NonResident { runs: Vec<DataRun>, size: u64 },
+}
+
+// $DATA stream of an entry, the unnamed stream when name is None. Streams moved to another
+// record by an attribute list are not followed
+pub fn locate(parser: &mut Parser, entry_id: u64, name: Option<&str>) -> crate::Result<Stream> {
+    let offset = entry_id * parser.record_size;
+    parser
+        .reader
+        .read_entry_bytes(offset, &mut parser.entry_bytes)
+        .map_err(|e| e.for_entry(entry_id))?;
+    let entry_bytes = parser.entry_bytes.as_slice();
+    let entry = Entry::from_entry_bytes(entry_bytes, offset, entry_id)?;
+    let attribute = entry
+        .attributes
+        .iter()
+        .find(|a| a.type_code == 0x80 && a.name.as_deref() == name)
+        .ok_or_else(|| crate::Error::missing_block("Data", entry_id))?;
+    let start = attribute.offset as usize;
+    let end = start + attribute.record_len as usize;
+    let truncated = || {
+        crate::Error::invalid_value(
+            "data",
+            "stream",
+            String::from("Stream is outside the entry"),
+        )
+        .in_entry(entry_id, offset)
+        .in_attribute(0x80)
+    };
+    match attribute.data {
+        AttributeData::Resident {
+            data_size,
+            data_offset,
+            ..
+        } => {
+            let data_start = start + data_offset as usize;
+            entry_bytes
+                .get(data_start..data_start + data_size as usize)
+                .map(|data| Stream::Resident(data.to_vec()))
+                .ok_or_else(truncated)
+        }
+        AttributeData::NonResident {
+            data_run_offset,
+            data_size,
+            ..
+        } => {
+            let runs = entry_bytes
+                .get(start + data_run_offset as usize..end)
+                .ok_or_else(truncated)?;
+            let runs = decode_data_runs(runs).map_err(|e| {
+                e.relative_to(start as u64 + data_run_offset as u64)
+                    .in_entry(entry_id, offset)
+                    .in_attribute(0x80)
+            })?;
+            Ok(Stream::NonResident {
+                runs,
+                size: data_size,
+            })
+        }
+    }
+}
+
+// Largest cluster NTFS supports
+pub const MAX_CLUSTER_SIZE: u64 = 2 * 1024 * 1024;
+
+// A volume image (or device) that non-resident streams are read from
+pub struct Volume<R: Read + Seek> {
+    reader: R,
+    pub cluster_size: u64,
+}
+
+impl<R: Read + Seek> Volume<R> {
+    // Takes the cluster size from the NTFS boot sector at the start of the image
+    pub fn new(mut reader: R) -> crate::Result<Self> {
+        let mut boot_sector = [0; 512];
+        reader.seek(SeekFrom::Start(0))?;
+        reader
+            .read_exact(&mut boot_sector)
+            .map_err(|e| crate::Error::into_buffer_fill_error(e.into(), 0, 512))?;
+        if &boot_sector[3..11] != b"NTFS    " {
+            return Err(crate::Error::InvalidSetting(String::from(
+                "Volume image does not start with an NTFS boot sector",
+            )));
+        }
+        let bytes_per_sector = LittleEndian::read_u16(&boot_sector[0x0B..0x0D]) as u64;
+        // Values above 0x80 are a negative power of two
+        let sectors_per_cluster = match boot_sector[0x0D] {
+            n if n > 0x80 => 1u64.checked_shl(256 - n as u32).unwrap_or(0),
+            n => n as u64,
+        };
+        // Garbage boot sectors can overflow, with_cluster_size rejects the 0 this turns into
+        let cluster_size = bytes_per_sector
+            .checked_mul(sectors_per_cluster)
+            .unwrap_or(0);
+        Self::with_cluster_size(reader, cluster_size)
+    }
+
+    // A cluster is read into memory at a time, so sizes NTFS can not have are rejected
+    pub fn with_cluster_size(reader: R, cluster_size: u64) -> crate::Result<Self> {
+        if cluster_size == 0 || cluster_size > MAX_CLUSTER_SIZE {
+            return Err(crate::Error::InvalidSetting(format!(
+                "Cluster size {} is not between 1 byte and 2 MiB",
+                cluster_size
+            )));
+        }
+        Ok(Self {
+            reader,
+            cluster_size,
+        })
+    }
+
+    // Writes the first size bytes of the runs, sparse runs are written as zeroes
+    pub fn copy_to<W: Write>(
+        &mut self,
+        runs: &[DataRun],
+        size: u64,
+        writer: &mut W,
+    ) -> crate::Result<u64> {
+        let mut remaining = size;
+        let mut buffer = vec![0; self.cluster_size as usize];
+        for run in runs {
+            let mut run_remaining = run
+                .clusters
+                .saturating_mul(self.cluster_size)
+                .min(remaining);
+            remaining -= run_remaining;
+            if let Some(lcn) = run.lcn {
+                self.reader
+                    .seek(SeekFrom::Start(lcn.saturating_mul(self.cluster_size)))?;
+            }
+            while run_remaining > 0 {
+                let chunk = &mut buffer[..run_remaining.min(self.cluster_size) as usize];
+                match run.lcn {
+                    Some(lcn) => self.reader.read_exact(chunk).map_err(|e| {
+                        let offset = lcn.saturating_mul(self.cluster_size);
+                        crate::Error::into_buffer_fill_error(e.into(), offset, chunk.len() as u64)
+                    })?,
+                    None => chunk.fill(0),
+                }
+                writer.write_all(chunk)?;
+                run_remaining -= chunk.len() as u64;
+            }
+        }
+        Ok(size - remaining)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, BASE_TIME, FLAG_IN_USE};
+    use std::io::Cursor;
+
+    #[test]
+    fn decode_data_runs_test() {
+        // 2 clusters at 0x10, 1 sparse cluster, 1 cluster 4 back from the first run
+        let runs = decode_data_runs(&[0x11, 0x02, 0x10, 0x01, 0x01, 0x11, 0x01, 0xFC, 0x00]);
+        assert_eq!(
+            runs.unwrap(),
+            vec![
+                DataRun {
+                    lcn: Some(0x10),
+                    clusters: 2
+                },
+                DataRun {
+                    lcn: None,
+                    clusters: 1
+                },
+                DataRun {
+                    lcn: Some(0x0C),
+                    clusters: 1
+                },
+            ]
+        );
+        assert!(decode_data_runs(&[0x11, 0x02]).is_err());
+        assert!(decode_data_runs(&[0x11, 0x01, 0x80, 0x00]).is_err());
+    }
+
+    #[test]
+    fn read_streams() {
+        let times = fixtures::Times::all(BASE_TIME);
+        let big = fixtures::record(
+            17,
+            1,
+            FLAG_IN_USE,
+            &[
+                fixtures::standard_information(&times, 0x20),
+                fixtures::file_name(5, 1, "big.bin", 1, &times, 6000),
+                fixtures::non_resident_data(None, &[0x11, 0x01, 0x02, 0x01, 0x01, 0x00], 6000),
+            ],
+        );
+        let mft = fixtures::volume(&[fixtures::file(16, 5, "a.txt", b"hello", BASE_TIME), big]);
+        let path = fixtures::write_temp("streams", &mft);
+        let mut parser = Parser::new(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let resident = locate(&mut parser, 16, None).unwrap();
+        assert_eq!(resident, Stream::Resident(b"hello".to_vec()));
+        assert!(locate(&mut parser, 16, Some("Zone.Identifier")).is_err());
+        let (runs, size) = match locate(&mut parser, 17, None).unwrap() {
+            Stream::NonResident { runs, size } => (runs, size),
+            stream => panic!("Expected a non-resident stream, got {:?}", stream),
+        };
+        // Boot sector with 512 byte sectors and 8 sectors per cluster, cluster 2 is all 0xAB
+        let mut image = vec![0; 4096 * 3];
+        image[3..11].copy_from_slice(b"NTFS    ");
+        image[0x0B..0x0D].copy_from_slice(&512u16.to_le_bytes());
+        image[0x0D] = 8;
+        image[8192..].fill(0xAB);
+        let mut volume = Volume::new(Cursor::new(image)).unwrap();
+        assert_eq!(volume.cluster_size, 4096);
+        let mut content = Vec::new();
+        assert_eq!(volume.copy_to(&runs, size, &mut content).unwrap(), 6000);
+        assert!(content[..4096].iter().all(|b| *b == 0xAB));
+        assert!(content[4096..].iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn corrupt_boot_sectors() {
+        let boot_sector = |bytes_per_sector: u16, sectors_per_cluster: u8| {
+            let mut image = vec![0; 512];
+            image[3..11].copy_from_slice(b"NTFS    ");
+            image[0x0B..0x0D].copy_from_slice(&bytes_per_sector.to_le_bytes());
+            image[0x0D] = sectors_per_cluster;
+            Volume::new(Cursor::new(image)).map(|v| v.cluster_size)
+        };
+        // 2^12 sectors per cluster
+        assert_eq!(boot_sector(512, 0xF4).unwrap(), 2 * 1024 * 1024);
+        // 2^63 sectors overflows, 2^32 would need terabytes per cluster
+        assert!(boot_sector(512, 0xC1).is_err());
+        assert!(boot_sector(512, 0xE0).is_err());
+        assert!(boot_sector(u16::MAX, 0x80).is_err());
+        assert!(boot_sector(0, 8).is_err());
+    }
+}
@@ -0,0 +1,209 @@
+use chrono::{DateTime, Utc};
+use std::path::PathBuf;
+
+use crate::iter::Record;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum TimelineSource {
+    #[serde(rename = "$STANDARD_INFORMATION")]
+    StandardInformation,
+    #[serde(rename = "$FILE_NAME")]
+    FileName,
+    #[serde(rename = "Zone.Identifier")]
+    ZoneIdentifier,
+}
+
+impl TimelineSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimelineSource::StandardInformation => "$STANDARD_INFORMATION",
+            TimelineSource::FileName => "$FILE_NAME",
+            TimelineSource::ZoneIdentifier => "Zone.Identifier",
+        }
+    }
+}
+
+// A single point on the timeline, times that are equal within a source are merged into one event
+#[derive(Debug, Clone, Serialize)]
+pub struct TimelineEvent {
+    pub timestamp: DateTime<Utc>,
+    pub macb: String,
+    pub source: TimelineSource,
+    pub path: PathBuf,
+    pub entry_id: u64,
+    pub sequence_number: u16,
+    pub description: String,
+}
+
+impl TimelineEvent {
+    // date,time,timezone,MACB,source,sourcetype,type,user,host,short,desc,version,filename,inode,notes,format,extra
+    pub fn to_l2t_csv(&self) -> String {
+        // l2t_csv has no quoting, fields are sanitised instead
+        fn sanitise(field: &str) -> String {
+            field.replace([',', '\n', '\r'], " ")
+        }
+        let path = sanitise(&self.path.to_string_lossy());
+        format!(
+            "{},{},UTC,{},FILE,NTFS {},{},-,-,{},{},2,{},{}-{},-,mft,-",
+            self.timestamp.format("%m/%d/%Y"),
+            self.timestamp.format("%H:%M:%S"),
+            self.macb,
+            self.source.as_str(),
+            macb_description(&self.macb),
+            path,
+            sanitise(&self.description),
+            path,
+            self.entry_id,
+            self.sequence_number,
+        )
+    }
+}
+
+// Human readable version of a MACB string, e.g. "M..B" -> "Modified; Born"
+fn macb_description(macb: &str) -> String {
+    let names = ["Modified", "Accessed", "Changed", "Born"];
+    macb.chars()
+        .zip(names)
+        .filter(|(c, _)| *c != '.')
+        .map(|(_, name)| name)
+        .collect::<Vec<&str>>()
+        .join("; ")
+}
+
+// Groups MACB times by value, returning each distinct time with its MACB string
+fn group_macb(times: [DateTime<Utc>; 4]) -> Vec<(DateTime<Utc>, String)> {
+    let mut groups: Vec<(DateTime<Utc>, String)> = Vec::new();
+    for time in times {
+        if groups.iter().any(|(t, _)| *t == time) {
+            continue;
+        }
+        let macb = times
+            .iter()
+            .zip(['M', 'A', 'C', 'B'])
+            .map(|(t, c)| if *t == time { c } else { '.' })
+            .collect();
+        groups.push((time, macb));
+    }
+    groups
+}
+
+// Expands a record into one event per distinct timestamp and source
+pub fn events(record: &Record) -> Vec<TimelineEvent> {
+    let mut events = Vec::new();
+    let mut push = |timestamp, macb, source, description: String| {
+        events.push(TimelineEvent {
+            timestamp,
+            macb,
+            source,
+            path: record.path.clone(),
+            entry_id: record.entry_id,
+            sequence_number: record.sequence_number,
+            description,
+        })
+    };
+    let si_description = match record.timestomp.is_empty() {
+        true => String::from("$STANDARD_INFORMATION"),
+        false => format!(
+            "$STANDARD_INFORMATION (timestomp: {})",
+            record
+                .timestomp
+                .iter()
+                .map(|r| r.to_string())
+                .collect::<Vec<String>>()
+                .join(";")
+        ),
+    };
+    for (timestamp, macb) in group_macb([
+        record.modified,
+        record.accessed,
+        record.changed,
+        record.created,
+    ]) {
+        push(
+            timestamp,
+            macb,
+            TimelineSource::StandardInformation,
+            si_description.clone(),
+        );
+    }
+    for file_name in &record.file_names {
+        for (timestamp, macb) in group_macb([
+            file_name.modified,
+            file_name.accessed,
+            file_name.changed,
+            file_name.created,
+        ]) {
+            push(
+                timestamp,
+                macb,
+                TimelineSource::FileName,
+                format!(
+                    "$FILE_NAME {} (parent {})",
+                    file_name.name, file_name.parent_entry_id
+                ),
+            );
+        }
+    }
+    // Zone.Identifier has no timestamp of its own, the $FILE_NAME creation time is when the
+    // download was written to disk
+    if let (Some(zone), Some(created)) = (&record.zone_identifier, record.fn_created) {
+        let details = zone
+            .lines()
+            .filter(|l| l.starts_with("ZoneId=") || l.starts_with("HostUrl="))
+            .collect::<Vec<&str>>()
+            .join(" ");
+        push(
+            created,
+            String::from("...B"),
+            TimelineSource::ZoneIdentifier,
+            format!("Downloaded {}", details),
+        );
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fixtures::{self, Times, BASE_TIME, FLAG_IN_USE, SECOND};
+    use crate::{Formatted, OutputType, Parser, ParserSettings};
+
+    #[test]
+    fn timeline_test() {
+        let zone = b"[ZoneTransfer]\r\nZoneId=3\r\nHostUrl=https://example.com/a.exe\r\n";
+        let si = Times {
+            modified: BASE_TIME + 120 * SECOND,
+            accessed: BASE_TIME + 180 * SECOND,
+            ..Times::all(BASE_TIME + 60 * SECOND)
+        };
+        let fn_times = Times::all(BASE_TIME + 60 * SECOND);
+        let download = fixtures::record(
+            16,
+            1,
+            FLAG_IN_USE,
+            &[
+                fixtures::standard_information(&si, 0x20),
+                fixtures::file_name(5, 1, "A~1.EXE", 2, &fn_times, 0),
+                fixtures::file_name(5, 1, "a.exe", 1, &fn_times, 0),
+                fixtures::data(None, b"MZ"),
+                fixtures::data(Some("Zone.Identifier"), zone),
+            ],
+        );
+        let path = fixtures::write_temp("timeline", &fixtures::volume(&[download]));
+        let parser = Parser::with_settings(&path, ParserSettings::new().drive_char('C')).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let rows: Vec<String> =
+            Formatted::with_output_type(parser, OutputType::TimelineL2tCsv).collect();
+        let lines: Vec<&str> = rows[12].lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "01/01/2023,00:02:00,UTC,M...,FILE,NTFS $STANDARD_INFORMATION,Modified,-,-,C:/a.exe,$STANDARD_INFORMATION,2,C:/a.exe,16-1,-,mft,-",
+                "01/01/2023,00:03:00,UTC,.A..,FILE,NTFS $STANDARD_INFORMATION,Accessed,-,-,C:/a.exe,$STANDARD_INFORMATION,2,C:/a.exe,16-1,-,mft,-",
+                "01/01/2023,00:01:00,UTC,..CB,FILE,NTFS $STANDARD_INFORMATION,Changed; Born,-,-,C:/a.exe,$STANDARD_INFORMATION,2,C:/a.exe,16-1,-,mft,-",
+                "01/01/2023,00:01:00,UTC,MACB,FILE,NTFS $FILE_NAME,Modified; Accessed; Changed; Born,-,-,C:/a.exe,$FILE_NAME A~1.EXE (parent 5),2,C:/a.exe,16-1,-,mft,-",
+                "01/01/2023,00:01:00,UTC,MACB,FILE,NTFS $FILE_NAME,Modified; Accessed; Changed; Born,-,-,C:/a.exe,$FILE_NAME a.exe (parent 5),2,C:/a.exe,16-1,-,mft,-",
+                "01/01/2023,00:01:00,UTC,...B,FILE,NTFS Zone.Identifier,Born,-,-,C:/a.exe,Downloaded ZoneId=3 HostUrl=https://example.com/a.exe,2,C:/a.exe,16-1,-,mft,-",
+            ]
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use chrono::{DateTime, Utc};
+use std::fmt;
+
+use crate::attributes::{filetime_to_datetime, FileName, StandardInformation};
+use crate::iter::Record;
+
+// Reasons a record's timestamps look manipulated, the checks are heuristics and not proof
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimestompReason {
+    // The $STANDARD_INFORMATION creation time is earlier than the $FILE_NAME one
+    SiBeforeFnCreation,
+    // A $STANDARD_INFORMATION time has no sub-second part, common with timestomping tools
+    ZeroSubsecond,
+    // A time is earlier than the creation of the volume ($MFT)
+    BeforeVolumeCreation,
+    // Created before an entry that was allocated earlier in the MFT
+    EntryOrder,
+}
+
+impl fmt::Display for TimestompReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimestompReason::SiBeforeFnCreation => write!(f, "si_before_fn_creation"),
+            TimestompReason::ZeroSubsecond => write!(f, "zero_subsecond"),
+            TimestompReason::BeforeVolumeCreation => write!(f, "before_volume_creation"),
+            TimestompReason::EntryOrder => write!(f, "entry_order"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Analyzer {
+    pub volume_created: Option<DateTime<Utc>>,
+    // Entry ID and $FILE_NAME creation time of the last record never reused (sequence number 1)
+    previous_allocation: Option<(u64, DateTime<Utc>)>,
+}
+
+impl Analyzer {
+    pub fn new(volume_created: Option<DateTime<Utc>>) -> Self {
+        Self {
+            volume_created,
+            previous_allocation: None,
+        }
+    }
+
+    // Checks that only need the record itself, see Analyzer::check_entry_order for the rest
+    pub fn check(
+        &self,
+        standard_info: &StandardInformation,
+        file_name: Option<&FileName>,
+    ) -> Vec<TimestompReason> {
+        let mut reasons = Vec::new();
+        // Copying a file or extracting an archive keeps the old modified time, so only the
+        // creation times are compared
+        if let Some(file_name) = file_name {
+            if standard_info.creation_time_raw != 0
+                && standard_info.creation_time < file_name.creation_time
+            {
+                reasons.push(TimestompReason::SiBeforeFnCreation);
+            }
+        }
+        // A FILETIME of 0 is unset, not a time
+        let si_times_raw = [
+            standard_info.creation_time_raw,
+            standard_info.modification_time_raw,
+            standard_info.mft_modification_time_raw,
+            standard_info.access_time_raw,
+        ];
+        if si_times_raw.iter().any(|t| *t != 0 && t % 10_000_000 == 0) {
+            reasons.push(TimestompReason::ZeroSubsecond);
+        }
+        if let Some(volume_created) = self.volume_created {
+            let fn_times_raw = file_name
+                .map(|f| {
+                    vec![
+                        f.creation_time_raw,
+                        f.modification_time_raw,
+                        f.mft_modification_time_raw,
+                        f.access_time_raw,
+                    ]
+                })
+                .unwrap_or_default();
+            if si_times_raw
+                .iter()
+                .chain(&fn_times_raw)
+                .filter_map(|t| filetime_to_datetime(*t))
+                .any(|t| t < volume_created)
+            {
+                reasons.push(TimestompReason::BeforeVolumeCreation);
+            }
+        }
+        reasons
+    }
+
+    // Relies on records being checked in entry order, the Iterator does this after decoding
+    pub fn check_entry_order(&mut self, record: &Record) -> bool {
+        // Entries that have never been reused are allocated in creation order
+        let is_first_use = record.sequence_number == 1 && record.flags & 0x01 != 0;
+        let mut out_of_order = false;
+        if let (true, Some(fn_created)) = (is_first_use, record.fn_created) {
+            if let Some((previous_id, previous_created)) = self.previous_allocation {
+                out_of_order = previous_id < record.entry_id
+                    && record.created_raw != 0
+                    && record.created < previous_created
+                    && fn_created >= previous_created;
+            }
+            self.previous_allocation = Some((record.entry_id, fn_created));
+        }
+        out_of_order
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, Times, BASE_TIME, FLAG_IN_USE, SECOND};
+    use crate::{Iterator, Parser, ParserSettings};
+
+    fn file_with_times(entry: u32, si: Times, fn_times: Times) -> Vec<u8> {
+        fixtures::record(
+            entry,
+            1,
+            FLAG_IN_USE,
+            &[
+                fixtures::standard_information(&si, 0x20),
+                fixtures::file_name(5, 1, "file.txt", 1, &fn_times, 0),
+            ],
+        )
+    }
+
+    #[test]
+    fn timestomp_test() {
+        let half = SECOND / 2;
+        let mft = fixtures::volume(&[
+            // Untouched
+            fixtures::file(16, 5, "normal.txt", b"", BASE_TIME + 1000 * SECOND + half),
+            // SI created moved before FN and before the previous allocation
+            file_with_times(
+                17,
+                Times {
+                    created: BASE_TIME + 500 * SECOND + half,
+                    ..Times::all(BASE_TIME + 2000 * SECOND + half)
+                },
+                Times::all(BASE_TIME + 2000 * SECOND + half),
+            ),
+            // Whole second SI times, as set by most timestomping tools
+            file_with_times(
+                18,
+                Times::all(BASE_TIME - BASE_TIME % SECOND + 3000 * SECOND),
+                Times::all(BASE_TIME + 2999 * SECOND),
+            ),
+            // Before the volume existed
+            file_with_times(
+                19,
+                Times::all(BASE_TIME - 10 * 86400 * SECOND),
+                Times::all(BASE_TIME + 4000 * SECOND),
+            ),
+            // Copied, the old modified time is kept but the file is created as new
+            file_with_times(
+                20,
+                Times {
+                    modified: BASE_TIME + 100 * SECOND + half,
+                    ..Times::all(BASE_TIME + 5000 * SECOND + half)
+                },
+                Times::all(BASE_TIME + 5000 * SECOND + half),
+            ),
+            // Unset $STANDARD_INFORMATION times
+            file_with_times(
+                21,
+                Times::all(0),
+                Times::all(BASE_TIME + 6000 * SECOND + half),
+            ),
+        ]);
+        let path = fixtures::write_temp("timestomp", &mft);
+        for threads in [1, 3] {
+            let parser =
+                Parser::with_settings(&path, ParserSettings::new().threads(threads)).unwrap();
+            let reasons: Vec<Vec<TimestompReason>> = Iterator::from(parser)
+                .map(|r| r.unwrap().timestomp)
+                .collect();
+            check_reasons(reasons);
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    fn check_reasons(reasons: Vec<Vec<TimestompReason>>) {
+        assert!(reasons[..12].iter().all(|r| r.is_empty()));
+        assert_eq!(reasons[12], vec![]);
+        assert_eq!(
+            reasons[13],
+            vec![
+                TimestompReason::SiBeforeFnCreation,
+                TimestompReason::EntryOrder
+            ]
+        );
+        assert_eq!(reasons[14], vec![TimestompReason::ZeroSubsecond]);
+        assert_eq!(
+            reasons[15],
+            vec![
+                TimestompReason::SiBeforeFnCreation,
+                TimestompReason::BeforeVolumeCreation,
+                TimestompReason::EntryOrder
+            ]
+        );
+        assert_eq!(reasons[16], vec![]);
+        assert_eq!(reasons[17], vec![]);
+    }
+}
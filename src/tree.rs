@@ -0,0 +1,518 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+
+use crate::attributes::FileName;
+use crate::block::{Block, BlockType};
+use crate::raw::{Header, RecordStatus};
+use crate::Parser;
+
+// Entry of the root directory, its $FILE_NAME is its own parent
+pub const ROOT_ENTRY: u64 = 5;
+
+// A $FILE_NAME of an entry, an entry has one for each hard link and a DOS name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    pub name: String,
+    pub name_space: u8,
+    pub parent_entry_id: u64,
+    pub parent_sequence_number: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub entry_id: u64,
+    pub sequence_number: u16,
+    pub is_directory: bool,
+    pub is_deleted: bool,
+    // Unnamed $DATA size, or the $FILE_NAME size without one
+    pub size: u64,
+    // DOS names included, they are only listed as children for entries without any other name
+    pub links: Vec<Link>,
+}
+
+impl Node {
+    // NTFS bumps the sequence number when an entry is freed, so the children of a deleted
+    // directory still point at the sequence number it had before
+    pub(crate) fn is_parent_of(&self, parent_sequence_number: u16) -> bool {
+        self.is_directory
+            && (self.sequence_number == parent_sequence_number
+                || self.is_deleted
+                    && self.sequence_number == parent_sequence_number.wrapping_add(1))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Child {
+    pub entry_id: u64,
+    pub name: String,
+}
+
+// Totals for a subtree, entries with several hard links in it are counted once
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Aggregate {
+    pub files: u64,
+    pub directories: u64,
+    pub size: u64,
+}
+
+// Children of every directory, built from one pass over the MFT. Entries with a hard link in
+// several directories are a child of each
+#[derive(Debug, Default, Clone)]
+pub struct DirectoryTree {
+    pub nodes: BTreeMap<u64, Node>,
+    children: HashMap<u64, Vec<Child>>,
+    // Children by parent and upcased name, for lookup
+    names: HashMap<(u64, String), Vec<u64>>,
+    orphans: Vec<u64>,
+    // Entries that could not be read, they are reported when iterating
+    pub skipped: u64,
+}
+
+impl DirectoryTree {
+    pub fn build(parser: &mut Parser) -> Self {
+        let mut tree = Self::default();
+        // $FILE_NAME attributes moved to extension records by an attribute list belong to the base
+        let mut extension_links = Vec::new();
+        for entry_id in 0..parser.block_count() {
+            let block = match parser.block_at(entry_id) {
+                Ok(block) if block.status == RecordStatus::Valid && block.error.is_none() => block,
+                Ok(_) => continue,
+                Err(_) => {
+                    tree.skipped += 1;
+                    continue;
+                }
+            };
+            match Self::read_node(parser, &block) {
+                Ok((node, None)) => {
+                    tree.nodes.insert(entry_id, node);
+                }
+                Ok((node, Some(base))) => {
+                    extension_links.extend(node.links.into_iter().map(|link| (base, link)))
+                }
+                Err(e) => {
+                    debug!(
+                        "Leaving entry {} out of the directory tree: {}",
+                        entry_id, e
+                    );
+                    tree.skipped += 1;
+                }
+            }
+        }
+        for (base, link) in extension_links {
+            if let Some(node) = tree.nodes.get_mut(&base) {
+                node.links.push(link);
+            }
+        }
+        tree.link_children();
+        tree
+    }
+
+    // Node for an entry, with the base entry id when it is an extension record
+    fn read_node(parser: &mut Parser, block: &Block) -> crate::Result<(Node, Option<u64>)> {
+        let entry_block = block
+            .pointer(BlockType::Entry)
+            .ok_or_else(|| crate::Error::missing_block("EntryBlock", block.entry_id))?;
+        let entry_offset = entry_block.offset;
+        parser
+            .reader
+            .read_entry_bytes(entry_offset, &mut parser.entry_bytes)
+            .map_err(|e| e.for_entry(block.entry_id))?;
+        let entry_bytes = parser.entry_bytes.as_slice();
+        let header = Header::from_reader(&mut entry_block.reader(entry_bytes, entry_offset))
+            .map_err(|e| e.in_entry(block.entry_id, entry_offset))?;
+        let mut links = Vec::new();
+        let mut file_name_size = None;
+        for file_name_block in block.pointers(BlockType::FileName) {
+            let file_name =
+                FileName::from_reader(&mut file_name_block.reader(entry_bytes, entry_offset))
+                    .map_err(|e| e.in_attribute(0x30).in_entry(block.entry_id, entry_offset))?;
+            file_name_size = file_name_size.or(Some(file_name.real_size));
+            links.push(Link {
+                name: file_name.name,
+                name_space: file_name.name_space,
+                parent_entry_id: file_name.parent_file_reference.entry,
+                parent_sequence_number: file_name.parent_file_reference.sequence,
+            });
+        }
+        let base = match header.base_mft_record.entry {
+            0 => None,
+            base => Some(base),
+        };
+        let node = Node {
+            entry_id: block.entry_id,
+            sequence_number: header.sequence_number,
+            is_directory: header.flags & 0x02 != 0,
+            is_deleted: header.flags & 0x01 == 0,
+            size: block
+                .pointer(BlockType::Data)
+                .map(|b| b.size)
+                .or(file_name_size)
+                .unwrap_or_default(),
+            links,
+        };
+        Ok((node, base))
+    }
+
+    // Links every node to its parents, nodes the root can not reach are orphans
+    fn link_children(&mut self) {
+        let mut broken = Vec::new();
+        for node in self.nodes.values() {
+            let mut linked = false;
+            // A DOS name can be looked up but is not listed next to the long name it stands for
+            let dos_only = node.links.iter().all(|l| l.name_space == 2);
+            for link in &node.links {
+                if node.entry_id == ROOT_ENTRY && link.parent_entry_id == ROOT_ENTRY {
+                    linked = true;
+                    continue;
+                }
+                match self.nodes.get(&link.parent_entry_id) {
+                    Some(parent)
+                        if parent.entry_id != node.entry_id
+                            && parent.is_parent_of(link.parent_sequence_number) =>
+                    {
+                        linked = true;
+                        self.names
+                            .entry((link.parent_entry_id, upcase(&link.name)))
+                            .or_default()
+                            .push(node.entry_id);
+                        if link.name_space != 2 || dos_only {
+                            self.children
+                                .entry(link.parent_entry_id)
+                                .or_default()
+                                .push(Child {
+                                    entry_id: node.entry_id,
+                                    name: link.name.clone(),
+                                });
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            if !linked && !node.links.is_empty() {
+                broken.push(node.entry_id);
+            }
+        }
+        for children in self.children.values_mut() {
+            children.sort_by(|a, b| {
+                (a.name.to_lowercase(), a.entry_id).cmp(&(b.name.to_lowercase(), b.entry_id))
+            });
+        }
+        // Directories whose parent links form a loop are linked but never reached from the root,
+        // one entry of each loop is listed so everything is reachable from the root or an orphan
+        let mut reached = HashSet::from([ROOT_ENTRY]);
+        reached.extend(self.walk(ROOT_ENTRY).map(|e| e.entry_id));
+        let mut orphans = Vec::new();
+        let unreached = self.nodes.values().filter(|n| !n.links.is_empty());
+        for entry_id in broken
+            .into_iter()
+            .chain(unreached.map(|n| n.entry_id).collect::<Vec<u64>>())
+        {
+            if reached.insert(entry_id) {
+                reached.extend(self.walk(entry_id).map(|e| e.entry_id));
+                orphans.push(entry_id);
+            }
+        }
+        self.orphans = orphans;
+    }
+
+    pub fn node(&self, entry_id: u64) -> Option<&Node> {
+        self.nodes.get(&entry_id)
+    }
+
+    // Children sorted by name, ignoring case
+    pub fn children(&self, entry_id: u64) -> &[Child] {
+        self.children
+            .get(&entry_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    // Entries with a name that can not be reached from the root, e.g. when the parent directory was
+    // deleted and its entry reused
+    pub fn orphans(&self) -> &[u64] {
+        &self.orphans
+    }
+
+    // Entries with a path, deleted ones included. Names are compared the way NTFS does, ignoring
+    // case, and the path can use either separator and start with a drive or volume
+    pub fn lookup(&self, path: &str) -> Vec<u64> {
+        let mut found = vec![ROOT_ENTRY];
+        for name in path_components(path) {
+            let name = upcase(name);
+            let mut next: Vec<u64> = found
+                .iter()
+                .filter_map(|parent| self.names.get(&(*parent, name.clone())))
+                .flatten()
+                .copied()
+                .collect();
+            next.sort_unstable();
+            next.dedup();
+            found = next;
+        }
+        found
+    }
+
+    // Depth first walk of everything below a directory, children in name order
+    pub fn walk(&self, entry_id: u64) -> Walk<'_> {
+        Walk {
+            tree: self,
+            stack: vec![(PathBuf::new(), self.children(entry_id).iter())],
+            visited: HashSet::from([entry_id]),
+            max_depth: None,
+        }
+    }
+
+    pub fn aggregate(&self, entry_id: u64) -> Aggregate {
+        let mut aggregate = Aggregate::default();
+        let mut counted = HashSet::new();
+        for entry in self.walk(entry_id) {
+            if !counted.insert(entry.entry_id) {
+                continue;
+            }
+            match self.nodes.get(&entry.entry_id) {
+                Some(node) if node.is_directory => aggregate.directories += 1,
+                Some(node) => {
+                    aggregate.files += 1;
+                    aggregate.size += node.size;
+                }
+                None => {}
+            }
+        }
+        aggregate
+    }
+}
+
+// NTFS compares names by upcasing each UTF-16 unit with the volume's $UpCase table, characters
+// without a single uppercase character are left as they are
+fn upcase(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            let mut upper = c.to_uppercase();
+            match (upper.next(), upper.next()) {
+                (Some(u), None) if u.len_utf16() == c.len_utf16() => u,
+                _ => c,
+            }
+        })
+        .collect()
+}
+
+// Names below the root in a path like C:\Windows, /Windows, {Root}/Windows,
+// \\?\C:\Windows, \VOLUME{id}\Windows (prefetch) or \Device\HarddiskVolume2\Windows
+fn path_components(path: &str) -> Vec<&str> {
+    let mut components: Vec<&str> = path
+        .split(['/', '\\'])
+        .filter(|c| !c.is_empty() && *c != "." && *c != "?" && *c != "??")
+        .collect();
+    let prefix = match components.as_slice() {
+        [device, volume, ..]
+            if device.eq_ignore_ascii_case("device")
+                && volume.to_ascii_lowercase().starts_with("harddiskvolume") =>
+        {
+            2
+        }
+        [first, ..]
+            if first.ends_with(':')
+                || first.eq_ignore_ascii_case("{root}")
+                || first.to_ascii_uppercase().starts_with("VOLUME{") =>
+        {
+            1
+        }
+        _ => 0,
+    };
+    components.split_off(prefix)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalkEntry<'a> {
+    pub entry_id: u64,
+    pub name: &'a str,
+    // 1 for the children of the directory the walk started at
+    pub depth: usize,
+    // Relative to the directory the walk started at
+    pub path: PathBuf,
+}
+
+pub struct Walk<'a> {
+    tree: &'a DirectoryTree,
+    stack: Vec<(PathBuf, std::slice::Iter<'a, Child>)>,
+    visited: HashSet<u64>,
+    max_depth: Option<usize>,
+}
+
+impl Walk<'_> {
+    // Stops descending below the given depth, 1 only yields the children of the start
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+}
+
+impl<'a> Iterator for Walk<'a> {
+    type Item = WalkEntry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let depth = self.stack.len();
+            let (parent_path, children) = self.stack.last_mut()?;
+            let child = match children.next() {
+                Some(child) => child,
+                None => {
+                    self.stack.pop();
+                    continue;
+                }
+            };
+            let path = parent_path.join(&child.name);
+            let is_directory = self
+                .tree
+                .node(child.entry_id)
+                .is_some_and(|n| n.is_directory);
+            // Directories are only entered once as parent links can loop in a corrupt MFT
+            if is_directory
+                && self.max_depth.is_none_or(|max| depth < max)
+                && self.visited.insert(child.entry_id)
+            {
+                let children = self.tree.children(child.entry_id).iter();
+                self.stack.push((path.clone(), children));
+            }
+            return Some(WalkEntry {
+                entry_id: child.entry_id,
+                name: &child.name,
+                depth,
+                path,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, Times, BASE_TIME, FLAG_DIRECTORY, FLAG_IN_USE};
+
+    fn entry(entry: u32, sequence: u16, flags: u16, names: &[(u64, u16, &str, u8)]) -> Vec<u8> {
+        let times = Times::all(BASE_TIME);
+        let mut attributes = vec![fixtures::standard_information(&times, 0x20)];
+        for (parent, parent_sequence, name, name_space) in names {
+            attributes.push(fixtures::file_name(
+                *parent,
+                *parent_sequence,
+                name,
+                *name_space,
+                &times,
+                10,
+            ));
+        }
+        fixtures::record(entry, sequence, flags, &attributes)
+    }
+
+    fn tree() -> DirectoryTree {
+        let dir = FLAG_IN_USE | FLAG_DIRECTORY;
+        let mft = fixtures::volume(&[
+            entry(16, 1, dir, &[(5, 1, "Users", 1)]),
+            entry(17, 1, dir, &[(16, 1, "bob", 1)]),
+            // Hard link in two directories with a DOS name
+            entry(
+                18,
+                1,
+                FLAG_IN_USE,
+                &[
+                    (17, 1, "Report.docx", 1),
+                    (17, 1, "REPORT~1.DOC", 2),
+                    (16, 1, "shared.docx", 1),
+                ],
+            ),
+            // Deleted directory and its deleted child, which points at the old sequence number
+            entry(19, 3, FLAG_DIRECTORY, &[(16, 1, "old", 1)]),
+            entry(20, 1, 0, &[(19, 2, "gone.txt", 1)]),
+            // Parent entry reused for a file
+            entry(21, 1, FLAG_IN_USE, &[(22, 1, "lost.txt", 1)]),
+            entry(22, 2, FLAG_IN_USE, &[(5, 1, "new.txt", 1)]),
+            // Directories that are each other's parent
+            entry(23, 1, dir, &[(24, 1, "a", 1)]),
+            entry(24, 1, dir, &[(23, 1, "b", 1)]),
+            // Deleted file with the name of a file in use
+            entry(25, 2, 0, &[(5, 1, "NEW.TXT", 1)]),
+            // Directory with a DOS name
+            entry(
+                26,
+                1,
+                dir,
+                &[(5, 1, "Program Files", 1), (5, 1, "PROGRA~1", 2)],
+            ),
+            entry(27, 1, FLAG_IN_USE, &[(26, 1, "app.exe", 3)]),
+        ]);
+        let path = fixtures::write_temp("directory_tree", &mft);
+        let mut parser = Parser::new(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        DirectoryTree::build(&mut parser)
+    }
+
+    #[test]
+    fn children_and_orphans() {
+        let tree = tree();
+        let names = |entry_id: u64| -> Vec<&str> {
+            tree.children(entry_id)
+                .iter()
+                .map(|c| c.name.as_str())
+                .collect()
+        };
+        assert_eq!(names(16), ["bob", "old", "shared.docx"]);
+        assert_eq!(names(17), ["Report.docx"]);
+        assert_eq!(names(19), ["gone.txt"]);
+        assert!(names(ROOT_ENTRY).contains(&"Users"));
+        assert!(!names(ROOT_ENTRY).contains(&"."));
+        assert!(names(ROOT_ENTRY).contains(&"Program Files"));
+        assert!(!names(ROOT_ENTRY).contains(&"PROGRA~1"));
+        assert_eq!(tree.node(18).unwrap().links.len(), 3);
+        assert_eq!(tree.orphans(), [21, 23]);
+        assert_eq!(tree.skipped, 0);
+    }
+
+    #[test]
+    fn walks_and_aggregates() {
+        let tree = tree();
+        let walk: Vec<(usize, String)> = tree
+            .walk(16)
+            .map(|e| (e.depth, e.path.display().to_string()))
+            .collect();
+        assert_eq!(
+            walk,
+            [
+                (1, String::from("bob")),
+                (2, String::from("bob/Report.docx")),
+                (1, String::from("old")),
+                (2, String::from("old/gone.txt")),
+                (1, String::from("shared.docx")),
+            ]
+        );
+        assert_eq!(tree.walk(16).max_depth(1).count(), 3);
+        // The loop is walked once
+        assert_eq!(tree.walk(23).count(), 2);
+        assert_eq!(
+            tree.aggregate(16),
+            Aggregate {
+                files: 2,
+                directories: 2,
+                size: 20,
+            }
+        );
+    }
+
+    #[test]
+    fn lookup_paths() {
+        let tree = tree();
+        assert_eq!(tree.lookup("C:\\Users\\BOB\\report.DOCX"), [18]);
+        assert_eq!(tree.lookup("/users/shared.docx"), [18]);
+        assert_eq!(tree.lookup("\\\\?\\C:\\Users\\old\\gone.txt"), [20]);
+        assert_eq!(tree.lookup("\\VOLUME{01d2a3b4-5c6d7e8f}\\USERS"), [16]);
+        assert_eq!(tree.lookup("\\Device\\HarddiskVolume2\\Users\\bob"), [17]);
+        assert_eq!(tree.lookup("{Root}/new.txt"), [22, 25]);
+        assert_eq!(tree.lookup("C:\\"), [ROOT_ENTRY]);
+        assert!(tree.lookup("C:/Users/bob/missing.txt").is_empty());
+        // DOS names are looked up like long ones
+        assert_eq!(tree.lookup("C:\\PROGRA~1\\app.exe"), [27]);
+        assert_eq!(tree.lookup("C:\\Users\\bob\\report~1.doc"), [18]);
+        // Only characters with a single uppercase character are folded
+        assert_eq!(upcase("straße.Txt"), "STRAßE.TXT");
+    }
+}
@@ -0,0 +1,592 @@
+use byteorder::{ByteOrder, LittleEndian};
+use chrono::{DateTime, Utc};
+use std::io::{BufReader, ErrorKind, Read};
+use std::path::PathBuf;
+
+use crate::attributes::filetime_to_datetime;
+use crate::csv::CsvWriter;
+use crate::raw::FileReference;
+use crate::tree::DirectoryTree;
+use crate::Parser;
+
+// Records bigger than this are taken as corrupt, a maximum length name is 510 bytes
+const MAX_RECORD_SIZE: usize = 0x1000;
+
+pub const REASONS: [(u32, &str); 24] = [
+    (0x0000_0001, "DATA_OVERWRITE"),
+    (0x0000_0002, "DATA_EXTEND"),
+    (0x0000_0004, "DATA_TRUNCATION"),
+    (0x0000_0010, "NAMED_DATA_OVERWRITE"),
+    (0x0000_0020, "NAMED_DATA_EXTEND"),
+    (0x0000_0040, "NAMED_DATA_TRUNCATION"),
+    (0x0000_0100, "FILE_CREATE"),
+    (0x0000_0200, "FILE_DELETE"),
+    (0x0000_0400, "EA_CHANGE"),
+    (0x0000_0800, "SECURITY_CHANGE"),
+    (0x0000_1000, "RENAME_OLD_NAME"),
+    (0x0000_2000, "RENAME_NEW_NAME"),
+    (0x0000_4000, "INDEXABLE_CHANGE"),
+    (0x0000_8000, "BASIC_INFO_CHANGE"),
+    (0x0001_0000, "HARD_LINK_CHANGE"),
+    (0x0002_0000, "COMPRESSION_CHANGE"),
+    (0x0004_0000, "ENCRYPTION_CHANGE"),
+    (0x0008_0000, "OBJECT_ID_CHANGE"),
+    (0x0010_0000, "REPARSE_POINT_CHANGE"),
+    (0x0020_0000, "STREAM_CHANGE"),
+    (0x0040_0000, "TRANSACTED_CHANGE"),
+    (0x0080_0000, "INTEGRITY_CHANGE"),
+    (0x0100_0000, "DESIRED_STORAGE_CLASS_CHANGE"),
+    (0x8000_0000, "CLOSE"),
+];
+
+pub const SOURCE_INFO: [(u32, &str); 4] = [
+    (0x1, "DATA_MANAGEMENT"),
+    (0x2, "AUXILIARY_DATA"),
+    (0x4, "REPLICATION_MANAGEMENT"),
+    (0x8, "CLIENT_REPLICATION_MANAGEMENT"),
+];
+
+pub const FILE_ATTRIBUTES: [(u32, &str); 20] = [
+    (0x0000_0001, "READONLY"),
+    (0x0000_0002, "HIDDEN"),
+    (0x0000_0004, "SYSTEM"),
+    (0x0000_0010, "DIRECTORY"),
+    (0x0000_0020, "ARCHIVE"),
+    (0x0000_0040, "DEVICE"),
+    (0x0000_0080, "NORMAL"),
+    (0x0000_0100, "TEMPORARY"),
+    (0x0000_0200, "SPARSE_FILE"),
+    (0x0000_0400, "REPARSE_POINT"),
+    (0x0000_0800, "COMPRESSED"),
+    (0x0000_1000, "OFFLINE"),
+    (0x0000_2000, "NOT_CONTENT_INDEXED"),
+    (0x0000_4000, "ENCRYPTED"),
+    (0x0000_8000, "INTEGRITY_STREAM"),
+    (0x0001_0000, "VIRTUAL"),
+    (0x0002_0000, "NO_SCRUB_DATA"),
+    (0x0004_0000, "RECALL_ON_OPEN"),
+    (0x0008_0000, "PINNED"),
+    (0x0040_0000, "RECALL_ON_DATA_ACCESS"),
+];
+
+// Names of the bits set in flags, unknown bits are left out
+pub fn flag_names(flags: u32, names: &[(u32, &'static str)]) -> Vec<&'static str> {
+    names
+        .iter()
+        .filter(|(bit, _)| flags & bit != 0)
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+// How a reference in a USN record relates to the entry in the MFT
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryMatch {
+    InUse,
+    // Freeing an entry bumps its sequence number, so a deleted file is one behind
+    Deleted,
+    // The entry now holds another file, its name and path are not the referenced file's
+    Reused,
+    // Not in the MFT, or without a $FILE_NAME
+    Missing,
+}
+
+impl EntryMatch {
+    pub fn of(tree: &DirectoryTree, entry_id: u64, sequence_number: u16) -> Self {
+        match tree.node(entry_id) {
+            None => EntryMatch::Missing,
+            Some(node) if node.sequence_number == sequence_number && !node.is_deleted => {
+                EntryMatch::InUse
+            }
+            Some(node)
+                if node.is_deleted
+                    && (node.sequence_number == sequence_number
+                        || node.sequence_number == sequence_number.wrapping_add(1)) =>
+            {
+                EntryMatch::Deleted
+            }
+            Some(_) => EntryMatch::Reused,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EntryMatch::InUse => "in_use",
+            EntryMatch::Deleted => "deleted",
+            EntryMatch::Reused => "reused",
+            EntryMatch::Missing => "missing",
+        }
+    }
+
+    // Whether the entry's names in the MFT are the referenced file's
+    fn is_same_file(&self) -> bool {
+        matches!(self, EntryMatch::InUse | EntryMatch::Deleted)
+    }
+}
+
+// Range of a file that was written, V4 records are logged with range tracking enabled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct UsnExtent {
+    pub offset: i64,
+    pub length: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UsnRecord {
+    // Of the record in $J
+    pub offset: u64,
+    pub major_version: u16,
+    pub minor_version: u16,
+    pub usn: u64,
+    // V4 records have no time, name, security ID or attributes
+    pub timestamp: Option<DateTime<Utc>>,
+    pub entry_id: u64,
+    pub sequence_number: u16,
+    pub parent_entry_id: u64,
+    pub parent_sequence_number: u16,
+    pub filename: Option<String>,
+    pub reason: u32,
+    pub reasons: Vec<&'static str>,
+    pub source_info: u32,
+    pub security_id: u32,
+    pub file_attributes: u32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extents: Vec<UsnExtent>,
+    // Set by correlate
+    pub path: Option<PathBuf>,
+    pub entry_match: Option<EntryMatch>,
+    pub parent_match: Option<EntryMatch>,
+}
+
+impl UsnRecord {
+    pub const CSV_HEADER: &'static str = "offset,usn,timestamp,entry_id,sequence_number,\
+        parent_entry_id,parent_sequence_number,filename,path,reasons,source_info,\
+        file_attributes,entry_match,parent_match,version";
+
+    // Record of a known version from a buffer holding exactly its length
+    fn from_buffer(offset: u64, buffer: &[u8]) -> Option<Self> {
+        let major_version = LittleEndian::read_u16(&buffer[4..6]);
+        // V3 and V4 use 128 bit file IDs, on NTFS they are the 64 bit reference zero extended
+        let (references, usn_offset) = match major_version {
+            2 => ([8, 16], 24),
+            3 | 4 => ([8, 24], 40),
+            _ => return None,
+        };
+        let minimum = match major_version {
+            2 => 0x3C,
+            3 => 0x4C,
+            _ => 0x40,
+        };
+        if buffer.len() < minimum {
+            return None;
+        }
+        let file = FileReference::from(LittleEndian::read_u64(&buffer[references[0]..]));
+        let parent = FileReference::from(LittleEndian::read_u64(&buffer[references[1]..]));
+        let u32_at = |at: usize| LittleEndian::read_u32(&buffer[at..at + 4]);
+        let mut record = Self {
+            offset,
+            major_version,
+            minor_version: LittleEndian::read_u16(&buffer[6..8]),
+            usn: LittleEndian::read_u64(&buffer[usn_offset..]),
+            timestamp: None,
+            entry_id: file.entry,
+            sequence_number: file.sequence,
+            parent_entry_id: parent.entry,
+            parent_sequence_number: parent.sequence,
+            filename: None,
+            reason: 0,
+            reasons: Vec::new(),
+            source_info: 0,
+            security_id: 0,
+            file_attributes: 0,
+            extents: Vec::new(),
+            path: None,
+            entry_match: None,
+            parent_match: None,
+        };
+        let fields = usn_offset + 8;
+        if major_version == 4 {
+            record.reason = u32_at(fields);
+            record.source_info = u32_at(fields + 4);
+            let count = LittleEndian::read_u16(&buffer[fields + 12..]) as usize;
+            let size = LittleEndian::read_u16(&buffer[fields + 14..]) as usize;
+            let start = fields + 16;
+            if size < 16 || start + count * size > buffer.len() {
+                return None;
+            }
+            record.extents = (0..count)
+                .map(|i| &buffer[start + i * size..])
+                .map(|extent| UsnExtent {
+                    offset: LittleEndian::read_i64(extent),
+                    length: LittleEndian::read_i64(&extent[8..]),
+                })
+                .collect();
+        } else {
+            record.timestamp = filetime_to_datetime(LittleEndian::read_u64(&buffer[fields..]));
+            record.reason = u32_at(fields + 8);
+            record.source_info = u32_at(fields + 12);
+            record.security_id = u32_at(fields + 16);
+            record.file_attributes = u32_at(fields + 20);
+            let name_length = LittleEndian::read_u16(&buffer[fields + 24..]) as usize;
+            let name_offset = LittleEndian::read_u16(&buffer[fields + 26..]) as usize;
+            let name = buffer.get(name_offset..name_offset + name_length)?;
+            let name: Vec<u16> = name.chunks_exact(2).map(LittleEndian::read_u16).collect();
+            record.filename = Some(String::from_utf16_lossy(&name));
+        }
+        record.reasons = flag_names(record.reason, &REASONS);
+        Some(record)
+    }
+
+    // Sets the path and how the file and parent references match the MFT. The path is the
+    // parent's path with the record's name, so deleted files and old names get one too
+    pub fn correlate(&mut self, parser: &mut Parser) {
+        let tree = parser.directory_tree();
+        let entry_match = EntryMatch::of(tree, self.entry_id, self.sequence_number);
+        let parent_match = EntryMatch::of(tree, self.parent_entry_id, self.parent_sequence_number);
+        self.entry_match = Some(entry_match);
+        self.parent_match = Some(parent_match);
+        self.path = match &self.filename {
+            Some(name) if parent_match.is_same_file() => {
+                parser
+                    .get_file_path(self.parent_entry_id)
+                    .ok()
+                    .map(|parent| {
+                        // The root's path ends with its own name, "."
+                        let parent = parent.to_string_lossy();
+                        let parent = parent.strip_suffix("/.").unwrap_or(&parent);
+                        PathBuf::from(format!("{}/{}", parent, name))
+                    })
+            }
+            None if entry_match.is_same_file() => parser.get_file_path(self.entry_id).ok(),
+            _ => None,
+        };
+    }
+
+    pub fn to_csv(&self) -> String {
+        let csv = CsvWriter::new(Vec::new());
+        let fields = [
+            self.offset.to_string(),
+            self.usn.to_string(),
+            self.timestamp.map(|t| t.to_rfc3339()).unwrap_or_default(),
+            self.entry_id.to_string(),
+            self.sequence_number.to_string(),
+            self.parent_entry_id.to_string(),
+            self.parent_sequence_number.to_string(),
+            self.filename.clone().unwrap_or_default(),
+            self.path
+                .as_ref()
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            self.reasons.join("|"),
+            flag_names(self.source_info, &SOURCE_INFO).join("|"),
+            flag_names(self.file_attributes, &FILE_ATTRIBUTES).join("|"),
+            self.entry_match
+                .map(|m| m.name())
+                .unwrap_or_default()
+                .to_string(),
+            self.parent_match
+                .map(|m| m.name())
+                .unwrap_or_default()
+                .to_string(),
+            format!("{}.{}", self.major_version, self.minor_version),
+        ];
+        fields
+            .iter()
+            .map(|f| csv.escape(f).to_string())
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+}
+
+// Records of a $UsnJrnl:$J stream. Extracted journals start with a sparse run of zeros and
+// records are 8 byte aligned, anything that is not a record is skipped 8 bytes at a time
+pub struct UsnJournal<R: Read> {
+    reader: BufReader<R>,
+    offset: u64,
+    // Bytes skipped that were not zero padding, e.g. a record cut off where the journal wrapped
+    pub skipped: u64,
+    done: bool,
+}
+
+impl<R: Read> UsnJournal<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            offset: 0,
+            skipped: 0,
+            done: false,
+        }
+    }
+
+    // Fills as much of the buffer as the input has left
+    fn fill(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        let mut read = 0;
+        while read < buffer.len() {
+            match self.reader.read(&mut buffer[read..]) {
+                Ok(0) => break,
+                Ok(n) => read += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(read)
+    }
+}
+
+impl<R: Read> Iterator for UsnJournal<R> {
+    type Item = crate::Result<UsnRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut buffer = [0; MAX_RECORD_SIZE];
+        while !self.done {
+            let read = match self.fill(&mut buffer[..8]) {
+                Ok(read) => read,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e.into()));
+                }
+            };
+            if read < 8 {
+                self.skipped += read as u64;
+                self.done = true;
+                break;
+            }
+            let offset = self.offset;
+            self.offset += 8;
+            let length = LittleEndian::read_u32(&buffer[..4]) as usize;
+            if length == 0 {
+                continue;
+            }
+            // Checked before reading the rest so a bad header only loses 8 bytes
+            let major_version = LittleEndian::read_u16(&buffer[4..6]);
+            if !(2..=4).contains(&major_version)
+                || length < 8
+                || !length.is_multiple_of(8)
+                || length > MAX_RECORD_SIZE
+            {
+                self.skipped += 8;
+                continue;
+            }
+            let read = match self.fill(&mut buffer[8..length]) {
+                Ok(read) => read,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e.into()));
+                }
+            };
+            self.offset += read as u64;
+            if read < length - 8 {
+                self.skipped += 8 + read as u64;
+                self.done = true;
+                break;
+            }
+            match UsnRecord::from_buffer(offset, &buffer[..length]) {
+                Some(record) => return Some(Ok(record)),
+                None => {
+                    trace!("No USN record at offset {} of the journal", offset);
+                    self.skipped += length as u64;
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, Times, BASE_TIME, FLAG_DIRECTORY, FLAG_IN_USE};
+    use crate::ParserSettings;
+
+    fn reference(entry: u64, sequence: u16) -> u64 {
+        entry | (sequence as u64) << 48
+    }
+
+    // V2 or V3 record, padded to 8 bytes
+    fn record(version: u16, file: u64, parent: u64, usn: u64, reason: u32, name: &str) -> Vec<u8> {
+        let name: Vec<u8> = name.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let mut record = vec![0; 8];
+        record[4..6].copy_from_slice(&version.to_le_bytes());
+        let id_size = if version == 2 { 8 } else { 16 };
+        for reference in [file, parent] {
+            let mut id = vec![0; id_size];
+            id[..8].copy_from_slice(&reference.to_le_bytes());
+            record.extend(id);
+        }
+        record.extend(usn.to_le_bytes());
+        record.extend(BASE_TIME.to_le_bytes());
+        record.extend(reason.to_le_bytes());
+        record.extend(0u32.to_le_bytes());
+        record.extend(0x109u32.to_le_bytes());
+        record.extend(0x21u32.to_le_bytes());
+        record.extend((name.len() as u16).to_le_bytes());
+        record.extend((record.len() as u16 + 2).to_le_bytes());
+        record.extend(name);
+        record.resize(record.len().div_ceil(8) * 8, 0);
+        let length = record.len() as u32;
+        record[..4].copy_from_slice(&length.to_le_bytes());
+        record
+    }
+
+    fn v4(file: u64, parent: u64, usn: u64, extents: &[(i64, i64)]) -> Vec<u8> {
+        let mut record = vec![0; 8];
+        record[4..6].copy_from_slice(&4u16.to_le_bytes());
+        for reference in [file, parent] {
+            record.extend(reference.to_le_bytes());
+            record.extend([0; 8]);
+        }
+        record.extend(usn.to_le_bytes());
+        record.extend(0x2u32.to_le_bytes());
+        record.extend(0u32.to_le_bytes());
+        record.extend(0u32.to_le_bytes());
+        record.extend((extents.len() as u16).to_le_bytes());
+        record.extend(16u16.to_le_bytes());
+        for (offset, length) in extents {
+            record.extend(offset.to_le_bytes());
+            record.extend(length.to_le_bytes());
+        }
+        let length = record.len() as u32;
+        record[..4].copy_from_slice(&length.to_le_bytes());
+        record
+    }
+
+    fn journal() -> Vec<u8> {
+        // Sparse start, a header with an unknown version and a record cut off at the end
+        let mut journal = vec![0; 4096];
+        journal.extend(record(
+            2,
+            reference(17, 1),
+            reference(16, 1),
+            4096,
+            0x100,
+            "a.txt",
+        ));
+        journal.extend([0x40, 0, 0, 0, 9, 0, 0, 0]);
+        journal.extend(record(
+            3,
+            reference(20, 1),
+            reference(16, 1),
+            4200,
+            0x8000_0200,
+            "gone.txt",
+        ));
+        journal.extend(record(
+            2,
+            reference(18, 1),
+            reference(5, 1),
+            4300,
+            0x100,
+            "old.txt",
+        ));
+        journal.extend(record(
+            2,
+            reference(22, 1),
+            reference(19, 1),
+            4400,
+            0x100,
+            "lost.txt",
+        ));
+        journal.extend(v4(
+            reference(17, 1),
+            reference(16, 1),
+            4500,
+            &[(0, 4096), (8192, 512)],
+        ));
+        journal.extend(&record(2, reference(17, 1), reference(16, 1), 4600, 0x2, "a.txt")[..24]);
+        journal
+    }
+
+    #[test]
+    fn journal_records() {
+        let mut journal = UsnJournal::new(std::io::Cursor::new(journal()));
+        let records: Vec<UsnRecord> = journal.by_ref().map(Result::unwrap).collect();
+        assert_eq!(journal.skipped, 8 + 24);
+        let usns: Vec<u64> = records.iter().map(|r| r.usn).collect();
+        assert_eq!(usns, [4096, 4200, 4300, 4400, 4500]);
+
+        let first = &records[0];
+        assert_eq!(first.offset, 4096);
+        assert_eq!((first.entry_id, first.sequence_number), (17, 1));
+        assert_eq!(first.filename.as_deref(), Some("a.txt"));
+        assert_eq!(first.reasons, ["FILE_CREATE"]);
+        assert_eq!(first.timestamp, filetime_to_datetime(BASE_TIME));
+        assert_eq!(records[1].major_version, 3);
+        assert_eq!(records[1].reasons, ["FILE_DELETE", "CLOSE"]);
+        assert_eq!(records[1].parent_entry_id, 16);
+        assert_eq!(records[4].timestamp, None);
+        assert_eq!(
+            records[4].extents[1],
+            UsnExtent {
+                offset: 8192,
+                length: 512
+            }
+        );
+    }
+
+    #[test]
+    fn correlate_with_mft() {
+        let times = Times::all(BASE_TIME);
+        let entry = |entry: u32, sequence: u16, flags: u16, parent: u64, name: &str| {
+            fixtures::record(
+                entry,
+                sequence,
+                flags,
+                &[
+                    fixtures::standard_information(&times, 0x20),
+                    fixtures::file_name(parent, 1, name, 1, &times, 0),
+                ],
+            )
+        };
+        let mft = fixtures::volume(&[
+            fixtures::directory(16, 5, "Users", BASE_TIME),
+            fixtures::file(17, 16, "a.txt", b"", BASE_TIME),
+            // Entry of old.txt reused
+            entry(18, 3, FLAG_IN_USE, 5, "new.txt"),
+            // Deleted directory, reused
+            entry(19, 4, FLAG_IN_USE | FLAG_DIRECTORY, 5, "other"),
+            entry(20, 2, 0, 16, "gone.txt"),
+        ]);
+        let path = fixtures::write_temp("usn", &mft);
+        let mut parser =
+            Parser::with_settings(&path, ParserSettings::new().drive_char('C')).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let mut records: Vec<UsnRecord> = UsnJournal::new(std::io::Cursor::new(journal()))
+            .map(Result::unwrap)
+            .collect();
+        for record in records.iter_mut() {
+            record.correlate(&mut parser);
+        }
+        let correlated: Vec<(Option<String>, EntryMatch, EntryMatch)> = records
+            .iter()
+            .map(|r| {
+                let path = r.path.as_ref().map(|p| p.display().to_string());
+                (path, r.entry_match.unwrap(), r.parent_match.unwrap())
+            })
+            .collect();
+        let some = |path: &str| Some(path.to_string());
+        assert_eq!(
+            correlated,
+            [
+                (some("C:/Users/a.txt"), EntryMatch::InUse, EntryMatch::InUse),
+                (
+                    some("C:/Users/gone.txt"),
+                    EntryMatch::Deleted,
+                    EntryMatch::InUse
+                ),
+                (some("C:/old.txt"), EntryMatch::Reused, EntryMatch::InUse),
+                (None, EntryMatch::Missing, EntryMatch::Reused),
+                // V4 records have no name, the entry's own path is used
+                (some("C:/Users/a.txt"), EntryMatch::InUse, EntryMatch::InUse),
+            ]
+        );
+        let row = records[1].to_csv();
+        assert!(row.starts_with("4176,4200,"));
+        assert!(row.contains(",gone.txt,C:/Users/gone.txt,FILE_DELETE|CLOSE,,READONLY|ARCHIVE,"));
+        assert!(row.ends_with(",deleted,in_use,3.0"));
+        assert_eq!(
+            UsnRecord::CSV_HEADER.split(',').count(),
+            row.split(',').count()
+        );
+    }
+}
@@ -0,0 +1,499 @@
+// Runs the mft-cli binary against synthetic MFTs, only built with the build-binary feature
+#![cfg(feature = "build-binary")]
+
+#[path = "../src/fixtures.rs"]
+mod fixtures;
+
+use fixtures::{BASE_TIME, FLAG_IN_USE};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+fn cli() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_mft-cli"))
+}
+
+// Removes the fixture when the test ends, even if it fails
+struct Fixture(PathBuf);
+
+impl Fixture {
+    fn new(name: &str, bytes: &[u8]) -> Self {
+        Self(fixtures::write_temp(&format!("cli-{}", name), bytes))
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn volume() -> Vec<u8> {
+    fixtures::volume(&[
+        fixtures::directory(16, 5, "Users", BASE_TIME),
+        fixtures::file(17, 16, "notes.txt", b"hello", BASE_TIME),
+        fixtures::directory(18, 5, "Windows", BASE_TIME),
+        fixtures::file(19, 18, "system.ini", b"[boot]", BASE_TIME),
+    ])
+}
+
+// Entry 16 has an attribute with an unknown form code so it cannot be parsed
+fn corrupt_volume() -> Vec<u8> {
+    let mut bad_form = fixtures::file(16, 5, "bad_form.txt", b"", BASE_TIME);
+    bad_form[0x38 + 8] = 5;
+    fixtures::volume(&[
+        bad_form,
+        fixtures::file(17, 5, "a.txt", b"hello", BASE_TIME),
+    ])
+}
+
+// volume() with a named stream on notes.txt, a deleted file and a non-resident file
+fn triage_volume() -> Vec<u8> {
+    let times = fixtures::Times::all(BASE_TIME);
+    let notes = fixtures::record(
+        17,
+        1,
+        FLAG_IN_USE,
+        &[
+            fixtures::standard_information(&times, 0x20),
+            fixtures::file_name(16, 1, "notes.txt", 1, &times, 5),
+            fixtures::data(None, b"hello"),
+            fixtures::data(Some("extra"), b"secret"),
+        ],
+    );
+    let deleted = fixtures::record(
+        20,
+        2,
+        0,
+        &[
+            fixtures::standard_information(&times, 0x20),
+            fixtures::file_name(16, 1, "old.txt", 1, &times, 0),
+        ],
+    );
+    // One cluster at LCN 1 of the image
+    let big = fixtures::record(
+        21,
+        1,
+        FLAG_IN_USE,
+        &[
+            fixtures::standard_information(&times, 0x20),
+            fixtures::file_name(18, 1, "big.bin", 1, &times, 5000),
+            fixtures::non_resident_data(None, &[0x11, 0x02, 0x01, 0x00], 5000),
+        ],
+    );
+    fixtures::volume(&[
+        fixtures::directory(16, 5, "Users", BASE_TIME),
+        notes,
+        fixtures::directory(18, 5, "Windows", BASE_TIME),
+        fixtures::file(19, 18, "system.ini", b"[boot]", BASE_TIME),
+        deleted,
+        big,
+    ])
+}
+
+// NTFS boot sector with 4096 byte clusters, followed by clusters filled with their LCN
+fn image() -> Vec<u8> {
+    let mut image: Vec<u8> = (0..3u8).flat_map(|lcn| vec![lcn; 4096]).collect();
+    image[..512].fill(0);
+    image[3..11].copy_from_slice(b"NTFS    ");
+    image[0x0B..0x0D].copy_from_slice(&512u16.to_le_bytes());
+    image[0x0D] = 8;
+    image
+}
+
+fn run(args: &[&str], input: &Path) -> Output {
+    cli().args(args).arg(input).output().unwrap()
+}
+
+fn subcommand(command: &str, input: &Path, args: &[&str]) -> Output {
+    cli().arg(command).arg(input).args(args).output().unwrap()
+}
+
+fn stdout(output: &Output) -> Vec<String> {
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(String::from)
+        .collect()
+}
+
+fn paths(output: &Output) -> Vec<String> {
+    stdout(output)
+        .into_iter()
+        .skip(1)
+        .filter_map(|line| line.split(',').nth(1).map(String::from))
+        .filter(|path| !path.is_empty())
+        .collect()
+}
+
+#[test]
+fn help_documents_exit_codes() {
+    let output = cli().arg("--help").output().unwrap();
+    assert!(output.status.success());
+    let help = String::from_utf8_lossy(&output.stdout);
+    for option in [
+        "--format",
+        "--output",
+        "--drive",
+        "--include",
+        "--filter",
+        "--fail-fast",
+    ] {
+        assert!(help.contains(option), "{} missing from help", option);
+    }
+    assert!(help.contains("Exit codes:"));
+}
+
+#[test]
+fn csv_with_drive_letter() {
+    let fixture = Fixture::new("csv", &volume());
+    let output = run(&["--drive", "d:", "--quiet"], &fixture.0);
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    let lines = stdout(&output);
+    assert!(lines[0].starts_with("entry_id,path,"));
+    assert!(lines.contains(&String::from("17,D:/Users/notes.txt,true,false,notes.txt,2023-01-01T00:00:00.123456700+00:00,2023-01-01T00:00:00.123456700+00:00,2023-01-01T00:00:00.123456700+00:00,,valid,")));
+}
+
+#[test]
+fn stdin_json_to_file() {
+    let output_path = std::env::temp_dir().join(format!("mft-{}-cli.json", std::process::id()));
+    let mut child = cli()
+        .args(["-", "--format", "json", "--drive", "C", "--output"])
+        .arg(&output_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(&volume()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    let written = std::fs::read_to_string(&output_path).unwrap();
+    std::fs::remove_file(&output_path).unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    let records: Vec<serde_json::Value> = written
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(records.len(), 16);
+    assert_eq!(records[15]["path"], "C:/Windows/system.ini");
+    assert_eq!(records[15]["size"], 6);
+}
+
+#[test]
+fn include_and_exclude_filters() {
+    let fixture = Fixture::new("filters", &volume());
+    let output = run(
+        &["-d", "C", "--include", "^C:/Users", "--include", "ini$"],
+        &fixture.0,
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        paths(&output),
+        ["C:/Users", "C:/Users/notes.txt", "C:/Windows/system.ini"]
+    );
+    // Both exclusions apply, the filename one used to overwrite the path one
+    let output = run(
+        &[
+            "-d",
+            "C",
+            "--exclude",
+            "/windows",
+            "--exclude",
+            "^C:/\\$",
+            "--exclude-name",
+            "\\.txt$",
+        ],
+        &fixture.0,
+    );
+    assert_eq!(paths(&output), ["C:/.", "C:/Users"]);
+    let output = run(
+        &["-d", "C", "--exclude-name", "^\\$|\\.(txt|ini)$"],
+        &fixture.0,
+    );
+    assert_eq!(paths(&output), ["C:/.", "C:/Users", "C:/Windows"]);
+    let output = run(
+        &[
+            "-d",
+            "C",
+            "--filter",
+            "not directory and entry_id > 15",
+            "--filter",
+            "ext in (txt, ini)",
+        ],
+        &fixture.0,
+    );
+    assert_eq!(
+        paths(&output),
+        ["C:/Users/notes.txt", "C:/Windows/system.ini"]
+    );
+    assert_eq!(
+        run(&["--filter", "size < lots"], &fixture.0).status.code(),
+        Some(2)
+    );
+}
+
+#[test]
+fn pattern_files_with_rule_hits() {
+    let fixture = Fixture::new("pattern-files", &volume());
+    let patterns = fixture.0.with_extension("patterns");
+    std::fs::write(&patterns, "# IOCs\n/USERS/\n\\.ini$\n").unwrap();
+    let output = cli()
+        .args(["-d", "C", "--rule-hits", "--include-name", "^windows$"])
+        .arg("--include-from")
+        .arg(&patterns)
+        .arg(&fixture.0)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&patterns).unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        paths(&output),
+        ["C:/Users/notes.txt", "C:/Windows", "C:/Windows/system.ini"]
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(&format!(
+        "path inclusion /USERS/ ({}:2): 1",
+        patterns.display()
+    )));
+    assert!(stderr.contains("filename inclusion ^windows$ (argument): 1"));
+    assert_eq!(
+        run(&["--include-from", "missing.txt"], &fixture.0)
+            .status
+            .code(),
+        Some(2)
+    );
+}
+
+#[test]
+fn corrupt_entries_and_fail_fast() {
+    let fixture = Fixture::new("corrupt", &corrupt_volume());
+    let report_path = fixture.0.with_extension("report.json");
+    let output = cli()
+        .args(["-d", "C", "--error-report"])
+        .arg(&report_path)
+        .arg(&fixture.0)
+        .output()
+        .unwrap();
+    let report: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&report_path).unwrap()).unwrap();
+    std::fs::remove_file(&report_path).unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output)
+        .iter()
+        .any(|l| l.starts_with("16,") && l.contains("Unknown attribute form code")));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Errors: 1 errors"));
+    assert_eq!(report["total"], 1);
+    assert_eq!(
+        report["samples"]["value_read"][0]["context"]["entry_id"],
+        16
+    );
+
+    let output = run(&["-d", "C", "--fail-fast"], &fixture.0);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(!stdout(&output).iter().any(|l| l.starts_with("17,")));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("entry 16"));
+}
+
+#[test]
+fn invalid_arguments_and_inputs() {
+    let fixture = Fixture::new("invalid", &volume());
+    assert_eq!(run(&["--format", "xml"], &fixture.0).status.code(), Some(2));
+    assert_eq!(run(&["--drive", "CD"], &fixture.0).status.code(), Some(2));
+    assert_eq!(run(&["--include", "("], &fixture.0).status.code(), Some(2));
+    assert_eq!(
+        run(&["--columns", "nope"], &fixture.0).status.code(),
+        Some(2)
+    );
+    let missing = fixture.0.with_extension("missing");
+    assert_eq!(run(&[], &missing).status.code(), Some(1));
+}
+
+#[test]
+fn stat_by_entry_and_path() {
+    let fixture = Fixture::new("stat", &triage_volume());
+    let output = subcommand("stat", &fixture.0, &["-d", "C", "17"]);
+    assert!(output.status.success());
+    let lines = stdout(&output);
+    for line in [
+        "Entry: 17",
+        "Path: C:/Users/notes.txt",
+        "Flags: 0x0001 (in use)",
+        "$FILE_NAME: notes.txt (namespace 1, parent 16-1)",
+        "  0x80 $DATA, instance 3, name extra, resident, 6 bytes",
+    ] {
+        assert!(lines.contains(&String::from(line)), "{} missing", line);
+    }
+    let by_path = subcommand("stat", &fixture.0, &["-d", "C", "\\users\\NOTES.TXT"]);
+    assert_eq!(by_path.stdout, output.stdout);
+    let output = subcommand("stat", &fixture.0, &["21"]);
+    assert!(stdout(&output).contains(&String::from(
+        "  0x80 $DATA, instance 2, non-resident, 5000 bytes (8192 allocated), VCN 0-1"
+    )));
+    assert_eq!(
+        subcommand("stat", &fixture.0, &["Users/missing.txt"])
+            .status
+            .code(),
+        Some(1)
+    );
+    assert_eq!(
+        subcommand("stat", &fixture.0, &["99"]).status.code(),
+        Some(1)
+    );
+}
+
+#[test]
+fn ls_and_tree() {
+    let fixture = Fixture::new("ls", &triage_volume());
+    let output = subcommand("ls", &fixture.0, &["/Users"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), ["f 17:\tnotes.txt", "f * 20:\told.txt"]);
+    let root = stdout(&subcommand("ls", &fixture.0, &["5"]));
+    assert!(root.contains(&String::from("d 16:\tUsers")));
+    assert!(!root.iter().any(|l| l.ends_with("\t.")));
+    assert_eq!(
+        subcommand("ls", &fixture.0, &["Users/notes.txt"])
+            .status
+            .code(),
+        Some(1)
+    );
+
+    let output = subcommand("tree", &fixture.0, &["-d", "C", "Windows"]);
+    assert_eq!(stdout(&output), ["C:/Windows", "  big.bin", "  system.ini"]);
+    let lines = stdout(&subcommand("tree", &fixture.0, &[]));
+    assert_eq!(lines[0], "{Root}/.");
+    assert!(lines.contains(&String::from("  Users/")));
+    assert!(lines.contains(&String::from("    notes.txt")));
+    let lines = stdout(&subcommand("tree", &fixture.0, &["--depth", "1"]));
+    assert!(lines.contains(&String::from("  Users/")));
+    assert!(!lines.contains(&String::from("    notes.txt")));
+}
+
+#[test]
+fn find_by_name_glob_and_regex() {
+    let fixture = Fixture::new("find", &triage_volume());
+    let find = |args: &[&str]| stdout(&subcommand("find", &fixture.0, args));
+    assert_eq!(
+        find(&["-d", "C", "--name", "NOTES.TXT"]),
+        ["C:/Users/notes.txt"]
+    );
+    assert_eq!(
+        find(&["-d", "C", "--glob", "*.t?t"]),
+        ["C:/Users/notes.txt", "C:/Users/old.txt"]
+    );
+    assert_eq!(
+        find(&["-d", "C", "--glob", "*.txt", "--regex", "^C:/Users/n"]),
+        ["C:/Users/notes.txt"]
+    );
+    assert!(find(&["--name", "nothing"]).is_empty());
+    assert_eq!(subcommand("find", &fixture.0, &[]).status.code(), Some(2));
+    assert_eq!(
+        subcommand("find", &fixture.0, &["--regex", "("])
+            .status
+            .code(),
+        Some(2)
+    );
+}
+
+#[test]
+fn cat_resident_named_and_image_streams() {
+    let fixture = Fixture::new("cat", &triage_volume());
+    let image = Fixture::new("cat-image", &image());
+    let cat = |args: &[&str]| subcommand("cat", &fixture.0, args);
+    assert_eq!(cat(&["17"]).stdout, b"hello");
+    assert_eq!(cat(&["-d", "C", "C:/Users/notes.txt"]).stdout, b"hello");
+    assert_eq!(cat(&["17:extra"]).stdout, b"secret");
+    assert_eq!(cat(&["/Users/notes.txt:extra"]).stdout, b"secret");
+    assert_eq!(cat(&["17:missing"]).status.code(), Some(1));
+    assert_eq!(cat(&["Windows/big.bin"]).status.code(), Some(2));
+
+    let output = cat(&["21", "--image", image.0.to_str().unwrap()]);
+    assert!(output.status.success());
+    assert_eq!(output.stdout.len(), 5000);
+    assert!(output.stdout[..4096].iter().all(|b| *b == 1));
+    assert!(output.stdout[4096..].iter().all(|b| *b == 2));
+}
+
+#[test]
+fn diff_snapshots() {
+    let old = Fixture::new("diff-old", &volume());
+    let new = Fixture::new("diff-new", &triage_volume());
+    let output = cli()
+        .args(["diff", "-d", "C"])
+        .arg(&old.0)
+        .arg(&new.0)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let lines = stdout(&output);
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("kind,entry_id,"));
+    assert!(lines[1].starts_with("created,21,1,false,C:/Windows/big.bin,,5000,,"));
+
+    let output = cli()
+        .args(["diff", "--json"])
+        .arg(&new.0)
+        .arg(&old.0)
+        .output()
+        .unwrap();
+    let lines = stdout(&output);
+    assert_eq!(lines.len(), 1);
+    let change: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+    assert_eq!(change["kind"], "deleted");
+    assert_eq!(change["path"], "{Root}/Windows/big.bin");
+}
+
+// USN_RECORD_V2 with the name at 0x3C
+fn usn_record(entry: u64, parent: u64, usn: u64, reason: u32, name: &str) -> Vec<u8> {
+    let name: Vec<u8> = name.encode_utf16().flat_map(u16::to_le_bytes).collect();
+    let mut record = vec![0; 0x3C];
+    record[4..6].copy_from_slice(&2u16.to_le_bytes());
+    record[8..16].copy_from_slice(&(entry | 1 << 48).to_le_bytes());
+    record[16..24].copy_from_slice(&(parent | 1 << 48).to_le_bytes());
+    record[24..32].copy_from_slice(&usn.to_le_bytes());
+    record[32..40].copy_from_slice(&BASE_TIME.to_le_bytes());
+    record[40..44].copy_from_slice(&reason.to_le_bytes());
+    record[56..58].copy_from_slice(&(name.len() as u16).to_le_bytes());
+    record[58..60].copy_from_slice(&0x3Cu16.to_le_bytes());
+    record.extend(name);
+    record.resize(record.len().div_ceil(8) * 8, 0);
+    let length = record.len() as u32;
+    record[..4].copy_from_slice(&length.to_le_bytes());
+    record
+}
+
+#[test]
+fn usn_journal_with_paths() {
+    let mft = Fixture::new("usn-mft", &triage_volume());
+    let mut journal = vec![0; 512];
+    journal.extend(usn_record(17, 16, 100, 0x100, "notes.txt"));
+    journal.extend(usn_record(20, 16, 200, 0x8000_0200, "old.txt"));
+    let journal = Fixture::new("usn-journal", &journal);
+    let output = cli()
+        .args(["usn", "-d", "C"])
+        .arg(&mft.0)
+        .arg(&journal.0)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let lines = stdout(&output);
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("offset,usn,timestamp,"));
+    assert!(lines[1].contains(",notes.txt,C:/Users/notes.txt,FILE_CREATE,"));
+    assert!(lines[2].starts_with("592,200,"));
+    assert!(lines[2].ends_with(",deleted,in_use,2.0"));
+
+    let output = cli()
+        .args(["usn", "--json"])
+        .arg(&mft.0)
+        .arg(&journal.0)
+        .output()
+        .unwrap();
+    let record: serde_json::Value = serde_json::from_str(&stdout(&output)[1]).unwrap();
+    assert_eq!(record["path"], "{Root}/Users/old.txt");
+    assert_eq!(
+        record["reasons"],
+        serde_json::json!(["FILE_DELETE", "CLOSE"])
+    );
+    assert_eq!(record["entry_match"], "deleted");
+}
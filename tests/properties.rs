@@ -0,0 +1,137 @@
+// Untrusted input must never panic, every malformed record has to surface as an Err or a
+// diagnostic row instead
+#[path = "../src/fixtures.rs"]
+mod fixtures;
+
+use fixtures::BASE_TIME;
+use mft::attributes::{AttributeList, Data, FileName, StandardInformation};
+use mft::block::Block;
+use mft::carve::CarvedRecord;
+use mft::filter::Filter;
+use mft::raw::Entry;
+use mft::usn::UsnJournal;
+use mft::{Formatted, Parser, ParserSettings};
+use proptest::prelude::*;
+use std::io::Cursor;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+fn sample_records() -> Vec<Vec<u8>> {
+    vec![
+        fixtures::file(16, 5, "a.txt", b"hello", BASE_TIME),
+        fixtures::directory(17, 5, "dir", BASE_TIME),
+        fixtures::file(18, 17, "b.txt", &[0x41; 300], BASE_TIME),
+    ]
+}
+
+fn mutate(mut record: Vec<u8>, mutations: &[(usize, u8)]) -> Vec<u8> {
+    for (offset, value) in mutations {
+        let len = record.len();
+        record[offset % len] = *value;
+    }
+    record
+}
+
+// Runs the parsing entry points over a single record buffer, results are ignored on purpose
+fn parse_record(bytes: &[u8]) {
+    let mut reader = Cursor::new(bytes.to_vec());
+    if let Ok(entry) = Entry::from_reader(&mut reader, None) {
+        let _ = Block::new_with_entry(&mut reader, &entry, 0);
+    }
+    let _ = CarvedRecord::from_entry_bytes(bytes, 0);
+}
+
+fn mutations() -> impl Strategy<Value = Vec<(usize, u8)>> {
+    prop::collection::vec((0usize..fixtures::RECORD_SIZE, any::<u8>()), 1..16)
+}
+
+proptest! {
+    #[test]
+    fn random_filters_do_not_panic(
+        expression in "(not |\\(|\\)|and |or |size|ext|modified|path|in|[<>=!~,'\" ]|[0-9a-z.:-]{1,4}){0,16}",
+    ) {
+        let _ = Filter::parse(&expression);
+    }
+
+    #[test]
+    fn random_journals_do_not_panic(
+        records in prop::collection::vec(
+            (1usize..80, 0u16..6, prop::collection::vec(any::<u8>(), 0..640)),
+            0..16,
+        ),
+    ) {
+        // Random bodies behind headers with plausible lengths and versions
+        let mut journal = Vec::new();
+        for (units, version, mut body) in records {
+            journal.extend(((units * 8) as u32).to_le_bytes());
+            journal.extend(version.to_le_bytes());
+            journal.extend([0, 0]);
+            body.resize(units * 8 - 8, 0);
+            journal.extend(body);
+        }
+        for record in UsnJournal::new(Cursor::new(journal)) {
+            prop_assert!(record.is_ok());
+        }
+    }
+
+    #[test]
+    fn random_bytes_do_not_panic(bytes in prop::collection::vec(any::<u8>(), 0..4096)) {
+        parse_record(&bytes);
+    }
+
+    #[test]
+    fn random_records_do_not_panic(
+        mut bytes in prop::collection::vec(any::<u8>(), fixtures::RECORD_SIZE),
+        signature in prop::sample::select(vec![*b"FILE", *b"BAAD"]),
+    ) {
+        // Random bytes almost never get past the signature check, give them a valid one
+        bytes[0..4].copy_from_slice(&signature);
+        parse_record(&bytes);
+    }
+
+    #[test]
+    fn mutated_records_do_not_panic(index in 0usize..3, mutations in mutations()) {
+        let record = mutate(sample_records().swap_remove(index), &mutations);
+        parse_record(&record);
+    }
+
+    #[test]
+    fn random_attributes_do_not_panic(
+        bytes in prop::collection::vec(any::<u8>(), 0..1024),
+        size in any::<u64>(),
+    ) {
+        let _ = StandardInformation::from_reader(&mut Cursor::new(&bytes));
+        let _ = FileName::from_reader(&mut Cursor::new(&bytes));
+        let _ = AttributeList::from_reader(&mut Cursor::new(&bytes), size);
+        let _ = Data::from_buffer(&bytes, true);
+    }
+}
+
+static VOLUMES: AtomicUsize = AtomicUsize::new(0);
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn mutated_volumes_do_not_panic(
+        mutations in prop::collection::vec(mutations(), 3),
+        threads in 1usize..3,
+    ) {
+        let extra: Vec<Vec<u8>> = sample_records()
+            .into_iter()
+            .zip(&mutations)
+            .map(|(record, mutations)| mutate(record, mutations))
+            .collect();
+        let name = format!("properties-{}", VOLUMES.fetch_add(1, Ordering::Relaxed));
+        let path = fixtures::write_temp(&name, &fixtures::volume(&extra));
+        let settings = ParserSettings::new()
+            .drive_char('C')
+            .scan_slack(true)
+            .threads(threads);
+        let parser = Parser::with_settings(&path, settings);
+        std::fs::remove_file(&path).unwrap();
+        if let Ok(parser) = parser {
+            let rows: Vec<String> = Formatted::from(parser).collect();
+            prop_assert!(!rows.is_empty());
+        }
+    }
+}